@@ -0,0 +1,22 @@
+// Evolves a small seeded grid and saves it as a PNG with one pixel per cell, shaded
+// by a closure passed to `render_to_rgba`: brightness is the dominant probability.
+//
+//     cargo run --example render_closure -- out.png
+use complex_conway::{dominant_state, io, render_to_rgba, Grid};
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "render_closure.png".to_string());
+
+    let mut grid = Grid::new_seeded(128, 128, 1).expect("Failed to create grid");
+    grid.evolve(20);
+
+    let rgba = render_to_rgba(&grid.cells, |cell| {
+        let p = cell.state_probabilities[dominant_state(&cell.state_probabilities)];
+        let v = (p * 255.0).round() as u8;
+        [v, v, v, 255]
+    });
+    io::save_png(&path, grid.width, grid.height, &rgba).expect("Failed to save PNG");
+    println!("Saved {}x{} cells to {}", grid.width, grid.height, path);
+}
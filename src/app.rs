@@ -0,0 +1,837 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{error, info, warn};
+use piston_window::rectangle::Rectangle;
+use piston_window::{
+    clear, line, rectangle, AdvancedWindow, Button, EventLoop, Key, MouseButton, MouseCursorEvent,
+    MouseScrollEvent, PistonWindow, PressEvent, ReleaseEvent, RenderEvent, Window, WindowSettings,
+};
+
+use crate::checkpoint::Checkpoints;
+#[cfg(feature = "clipboard")]
+use crate::clipboard;
+use crate::config::Config;
+use crate::grid::{reseed, CellState, DrivenSource, Grid, InitDistribution};
+use crate::history::History;
+use crate::io;
+use crate::render::{
+    contour_level, frame_rgba, Colormap, RenderMode, ToneMapping, View, ZOOM_MAX, ZOOM_MIN,
+    ZOOM_STEP,
+};
+use crate::stats::{FrameStats, TitleStats};
+
+// Limits on the evolution rate set with , and .
+const STEPS_PER_SECOND_MIN: f64 = 0.5;
+const STEPS_PER_SECOND_MAX: f64 = 240.0;
+const STEPS_PER_SECOND_STEP: f64 = 1.25; // Rate factor applied per key press
+
+const UNCAPPED_FPS: u64 = 10_000;
+const CHECKPOINT_BUDGET: usize = 64 * 1024 * 1024; // Checkpoint bytes kept in memory before spilling to disk
+const HISTORY_BUDGET: usize = 256 * 1024 * 1024; // Most bytes of rewind snapshots, whatever history_depth asks for
+
+// The interactive window: evolves the grid on a timer and draws it, with keyboard
+// and mouse controls for the view, render modes and edits to the grid
+pub struct App {
+    pub grid: Grid,
+    pub config: Config,
+    pub distribution: InitDistribution, // Used when K re-randomizes the grid
+    pub step_marker: bool,              // Draw a corner square that changes color every step
+    pub frame_stats: bool,              // Print frame time percentiles on exit
+    pub state_histogram: bool,          // Log the dominant state counts once a second
+}
+
+impl App {
+    // A uniformly random grid of the configured size, or an error if the config is invalid
+    pub fn new(config: Config) -> Result<App, String> {
+        config.validate()?;
+        let grid = Grid::with_entanglement(
+            config.grid_width,
+            config.grid_height,
+            InitDistribution::Uniform,
+            config.entanglement(),
+            None,
+        )?;
+        App::with_grid(grid, config)
+    }
+
+    // Runs an existing grid. Its evolution parameters are replaced by the config's, and
+    // its size replaces the configured one.
+    pub fn with_grid(mut grid: Grid, config: Config) -> Result<App, String> {
+        let config = Config {
+            grid_width: grid.width,
+            grid_height: grid.height,
+            ..config
+        };
+        config.validate()?;
+        grid.apply_config(&config);
+        Ok(App {
+            grid,
+            config,
+            distribution: InitDistribution::Uniform,
+            step_marker: false,
+            frame_stats: false,
+            state_histogram: false,
+        })
+    }
+
+    // Replaces the grid with a freshly drawn one of a new size, keeping its stencil,
+    // evolution parameters and the driven sources that still fit. Sizes the config would
+    // reject are refused before anything is allocated.
+    pub fn resize_grid(&mut self, new_width: usize, new_height: usize) -> Result<(), String> {
+        let config = Config {
+            grid_width: new_width,
+            grid_height: new_height,
+            ..self.config.clone()
+        };
+        config.validate()?;
+        self.grid = self
+            .grid
+            .resized(new_width, new_height, self.distribution)?;
+        self.config = config;
+        Ok(())
+    }
+
+    // Opens the window and runs until it is closed
+    pub fn run(mut self) {
+        let (mut grid_width, mut grid_height) = (self.grid.width, self.grid.height);
+        let (cell_size, distribution) = (self.config.cell_size, self.distribution);
+        let step_marker = self.step_marker;
+        // Evolution runs on wall-clock time, independent of the frame rate
+        let mut steps_per_second = (1000.0 / self.config.update_interval_ms.max(1) as f64)
+            .clamp(STEPS_PER_SECOND_MIN, STEPS_PER_SECOND_MAX);
+        let slow_motion_factor = self.config.slow_motion_factor;
+
+        // The window keeps its size when the grid is resized; the view zoom adapts instead.
+        // The user can still resize it, so its size is read again every frame.
+        let (window_width, window_height) = (grid_width * cell_size, grid_height * cell_size);
+        let mut initial_cells = self.grid.cells.clone(); // Snapshot for the difference render mode
+        let mut window: PistonWindow =
+            WindowSettings::new("Quantum Life", [window_width as u32, window_height as u32])
+                .exit_on_esc(true)
+                .vsync(self.config.vsync)
+                .samples(self.config.msaa_samples)
+                .build()
+                .unwrap_or_else(|e| panic!("Failed to build PistonWindow: {}", e));
+        // Piston always paces frames to some rate; "uncapped" just sets it out of reach
+        window.set_max_fps(match self.config.max_fps {
+            0 => UNCAPPED_FPS,
+            fps => fps,
+        });
+
+        let mut last_update = Instant::now();
+        let mut sim_step = 0;
+        let mut diverged = false; // Reported once, then flagged on screen
+        let mut reseeds = 0; // Times K re-randomized a seeded grid
+        let mut slow_motion = false;
+        let mut ctrl_held = false;
+        let mut alt_held = false;
+        let mut painting: Option<bool> = None; // Alt+drag paints (true) or erases (false) obstacles
+        let mut render_mode = RenderMode::DominantState;
+        let mut contour_levels = 10; // Number of iso-levels drawn in contour mode
+        let mut grid_lines = false; // Outline every cell, for telling cells apart on small grids
+        let mut tone = ToneMapping::new();
+        let mut colormap = Colormap::Classic;
+        let mut view = View::new(cell_size);
+        let mut cursor = [0.0, 0.0];
+        let mut dragging = false; // Middle mouse button held to pan
+        let mut checkpoints = Checkpoints::new(CHECKPOINT_BUDGET);
+        let mut history = History::new(
+            self.config.history_depth,
+            self.config.history_interval,
+            HISTORY_BUDGET,
+        );
+        let mut frame_stats = if self.frame_stats {
+            Some(FrameStats::new())
+        } else {
+            None
+        };
+        let mut title_stats = TitleStats::new();
+        let mut last_histogram = Instant::now();
+        let mut follow = false; // Auto-pan toward where the grid is changing most
+        let mut follow_target = None;
+        let mut follow_baseline = self.grid.cells.clone(); // Cells at the last centroid update
+        let mut last_follow = Instant::now();
+
+        // Beyond the grid's own buffers the window holds full copies of the cells: up to
+        // history_depth snapshots (fewer if HISTORY_BUDGET runs out first), the live state
+        // stashed while rewound, initial_cells and follow_baseline
+        let snapshots = history.capacity(&self.grid.cells);
+        let cell_copies = snapshots + 3;
+        info!(
+            "Window memory: up to {:.1} MB more for {} copies of the cells ({} history snapshots, live state, difference and follow baselines), plus up to {} MB of checkpoints",
+            (cell_copies * self.grid.memory_usage().cells) as f64 / (1024.0 * 1024.0),
+            cell_copies,
+            snapshots,
+            CHECKPOINT_BUDGET / (1024 * 1024)
+        );
+        let mut highlighted_pair: Vec<(usize, usize)> = Vec::new(); // Up to two cells picked with the mouse
+
+        while let Some(e) = window.next() {
+            let size = window.size();
+            let window_size = [size.width, size.height];
+            if let Some(Button::Keyboard(key)) = e.press_args() {
+                match key {
+                    Key::Y => view.flip_y = !view.flip_y, // Mirror the vertical mapping
+                    Key::LShift | Key::RShift => slow_motion = true,
+                    Key::LCtrl | Key::RCtrl => ctrl_held = true,
+                    Key::LAlt | Key::RAlt => alt_held = true,
+                    Key::V => {
+                        render_mode = render_mode.next();
+                        info!("Render mode: {:?}", render_mode);
+                    }
+                    Key::H => grid_lines = !grid_lines,
+                    Key::F => {
+                        follow = !follow;
+                        follow_baseline = self.grid.cells.clone();
+                        follow_target = None;
+                        last_follow = Instant::now();
+                        info!("Auto-follow {}", if follow { "on" } else { "off" });
+                    }
+                    Key::G => {
+                        colormap = colormap.next();
+                        info!("Colormap: {:?}", colormap);
+                    }
+                    Key::J => {
+                        tone.curve = tone.curve.next();
+                        info!("Tone curve: {:?}", tone.curve);
+                    }
+                    Key::Minus => tone.adjust_gamma(-0.1),
+                    Key::Equals => tone.adjust_gamma(0.1),
+                    Key::U => self.grid.adjust_collapse(-0.05),
+                    Key::I => self.grid.adjust_collapse(0.05),
+                    Key::D1 => self.grid.adjust_bistability(-0.05),
+                    Key::D2 => self.grid.adjust_bistability(0.05),
+                    Key::D3 => self.grid.adjust_hysteresis(-0.05),
+                    Key::D4 => self.grid.adjust_hysteresis(0.05),
+                    Key::D9 => self.grid.scale_randomness(0.5),
+                    Key::D0 => self.grid.scale_randomness(2.0),
+                    Key::T => {
+                        view.wrap = !view.wrap;
+                        info!(
+                            "View panning {}",
+                            if view.wrap {
+                                "wraps"
+                            } else {
+                                "clamped to grid edges"
+                            }
+                        );
+                    }
+                    Key::Home => {
+                        // Center on the middle of the grid at the current zoom
+                        let middle = (grid_width as f64 / 2.0, grid_height as f64 / 2.0);
+                        view.follow(middle, 1.0, grid_width, grid_height, window_size);
+                    }
+                    Key::Q => view.zoom_by(1.0 / ZOOM_STEP),
+                    Key::E => view.zoom_by(ZOOM_STEP),
+                    Key::K => {
+                        // Fresh random cells, keeping the view and the current stencil. A
+                        // seeded grid draws them from its seed and a reseed count, so the
+                        // run stays reproducible.
+                        let seed = self.grid.seed.map(|seed| {
+                            reseeds += 1;
+                            reseed(seed, reseeds)
+                        });
+                        match Grid::with_entanglement(
+                            grid_width,
+                            grid_height,
+                            distribution,
+                            self.grid.entanglement,
+                            seed,
+                        ) {
+                            Ok(fresh) => {
+                                self.grid.cells = fresh.cells;
+                                self.grid.steps = 0;
+                                self.grid.wake_all();
+                                history.clear();
+                                initial_cells = self.grid.cells.clone();
+                                sim_step = 0;
+                                diverged = false;
+                                info!("Randomized the grid");
+                            }
+                            Err(err) => error!("Failed to randomize grid: {}", err),
+                        }
+                    }
+                    Key::PageUp | Key::PageDown => {
+                        // Double or halve the grid, zooming so it still fills the window
+                        let (width, height) = if key == Key::PageUp {
+                            (grid_width * 2, grid_height * 2)
+                        } else {
+                            (grid_width / 2, grid_height / 2)
+                        };
+                        let zoom = window_size[0] / (width * cell_size) as f64;
+                        if height == 0 || !(ZOOM_MIN..=ZOOM_MAX).contains(&zoom) {
+                            info!(
+                                "Grid size is already at its limit of {}x{}",
+                                grid_width, grid_height
+                            );
+                        } else {
+                            match self.resize_grid(width, height) {
+                                Ok(()) => {
+                                    (grid_width, grid_height) = (width, height);
+                                    initial_cells = self.grid.cells.clone();
+                                    sim_step = 0;
+                                    diverged = false;
+                                    highlighted_pair.clear();
+                                    // Saved states no longer fit the grid
+                                    checkpoints = Checkpoints::new(CHECKPOINT_BUDGET);
+                                    history.clear();
+                                    follow_baseline = self.grid.cells.clone();
+                                    follow_target = None;
+                                    view.zoom = zoom;
+                                    view.pan_x = 0.0;
+                                    view.pan_y = 0.0;
+                                    info!("Resized grid to {}x{}", width, height);
+                                }
+                                Err(err) => error!("Failed to resize grid: {}", err),
+                            }
+                        }
+                    }
+                    Key::O => match self.grid.save("state.qcon") {
+                        Ok(()) => info!("Saved grid state to state.qcon"),
+                        Err(err) => error!("Failed to save grid state: {}", err),
+                    },
+                    Key::L => match Grid::load("state.qcon") {
+                        Ok(loaded)
+                            if (loaded.width, loaded.height) != (grid_width, grid_height) =>
+                        {
+                            error!(
+                                "state.qcon holds a {}x{} grid but the current grid is {}x{}",
+                                loaded.width, loaded.height, grid_width, grid_height
+                            );
+                        }
+                        Ok(loaded) => {
+                            self.grid.cells = loaded.cells;
+                            self.grid.wake_all();
+                            history.clear();
+                            info!("Loaded grid state from state.qcon");
+                        }
+                        Err(err) => error!("Failed to load grid state: {}", err),
+                    },
+                    Key::B => {
+                        self.grid.boundary = self.grid.boundary.next();
+                        self.grid.wake_all();
+                        info!("Boundary: {:?}", self.grid.boundary);
+                    }
+                    Key::Comma | Key::Period => {
+                        let factor = if key == Key::Period {
+                            STEPS_PER_SECOND_STEP
+                        } else {
+                            1.0 / STEPS_PER_SECOND_STEP
+                        };
+                        steps_per_second = (steps_per_second * factor)
+                            .clamp(STEPS_PER_SECOND_MIN, STEPS_PER_SECOND_MAX);
+                        info!("Evolution rate: {:.1} steps per second", steps_per_second);
+                    }
+                    Key::M => {
+                        self.grid.measure();
+                        info!("Measured the grid at step {}", sim_step);
+                    }
+                    Key::N => {
+                        self.grid.stencil = self.grid.stencil.next();
+                        self.grid.wake_all();
+                        info!("Neighbor stencil: {:?}", self.grid.stencil);
+                    }
+                    Key::Left => match history.back(sim_step, self.grid.steps, &self.grid.cells) {
+                        Some(snapshot) => {
+                            self.grid.cells = snapshot.cells.clone();
+                            self.grid.steps = snapshot.grid_steps;
+                            self.grid.wake_all();
+                            sim_step = snapshot.step;
+                            info!("Rewound to step {} (paused)", sim_step);
+                        }
+                        None => info!("No earlier state in the history"),
+                    },
+                    Key::Right => {
+                        if let Some(snapshot) = history.forward() {
+                            self.grid.cells = snapshot.cells.clone();
+                            self.grid.steps = snapshot.grid_steps;
+                            self.grid.wake_all();
+                            sim_step = snapshot.step;
+                            if history.is_rewound() {
+                                info!("Stepped forward to step {} (paused)", sim_step);
+                            } else {
+                                info!("Resumed at step {}", sim_step);
+                            }
+                        }
+                    }
+                    Key::F2 => {
+                        // The live settings as a config file, to save as a preset
+                        let mut config = self.config.clone();
+                        self.grid.write_config(&mut config);
+                        config.update_interval_ms = (1000.0 / steps_per_second).round() as u64;
+                        config.cell_size =
+                            ((view.cell_size as f64 * view.zoom).round() as usize).max(1);
+                        match config.to_toml() {
+                            Ok(text) => info!("Current settings:\n{}", text),
+                            Err(e) => error!("Failed to dump the settings: {}", e),
+                        }
+                    }
+                    Key::F5 => match checkpoints.push(&self.grid.cells) {
+                        Ok(()) => info!("Saved checkpoint {}", checkpoints.len()),
+                        Err(err) => error!("Failed to save checkpoint: {}", err),
+                    },
+                    Key::F9 => match checkpoints.pop() {
+                        Some(Ok(cells)) => {
+                            self.grid.cells = cells;
+                            self.grid.wake_all();
+                            history.clear();
+                            info!("Restored checkpoint {}", checkpoints.len() + 1);
+                        }
+                        Some(Err(err)) => error!("Failed to restore checkpoint: {}", err),
+                        None => info!("No checkpoints to restore"),
+                    },
+                    Key::P => {
+                        // Drop a wave packet under the cursor
+                        if let Some((i, j)) = view.cell_at(cursor, grid_width, grid_height) {
+                            self.grid
+                                .inject_wave_packet((j as f64, i as f64), 6.0, 0.8, 0.0);
+                        }
+                    }
+                    Key::LeftBracket if contour_levels > 1 => {
+                        contour_levels -= 1;
+                        info!("Contour levels: {}", contour_levels);
+                    }
+                    Key::RightBracket if contour_levels < 64 => {
+                        contour_levels += 1;
+                        info!("Contour levels: {}", contour_levels);
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some([_, scroll]) = e.mouse_scroll_args() {
+                // The wheel zooms around the cursor rather than the window corner
+                view.zoom_at(ZOOM_STEP.powf(scroll), cursor);
+            }
+
+            if let Some(pos) = e.mouse_cursor_args() {
+                if dragging {
+                    view.drag(pos[0] - cursor[0], pos[1] - cursor[1]);
+                }
+                cursor = pos;
+            }
+
+            if let Some(Button::Mouse(button)) = e.press_args() {
+                match button {
+                    MouseButton::Left if alt_held => painting = Some(true),
+                    MouseButton::Right if alt_held => painting = Some(false),
+                    MouseButton::Left if ctrl_held => {
+                        // Ctrl-click drops a driven source under the cursor
+                        if let Some((row, col)) = view.cell_at(cursor, grid_width, grid_height) {
+                            self.grid.sources.push(DrivenSource {
+                                row,
+                                col,
+                                amplitude: 1.0,
+                                frequency: 0.3,
+                                phase: 0.0,
+                            });
+                            info!("Added driven source at ({}, {})", col, row);
+                        }
+                    }
+                    MouseButton::Right if ctrl_held => {
+                        // Ctrl-right-click drops a sharp pulse so the ripples spreading from
+                        // it can be followed, in slow motion too while Shift is held
+                        if let Some((row, col)) = view.cell_at(cursor, grid_width, grid_height) {
+                            self.grid
+                                .inject_wave_packet((col as f64, row as f64), 1.5, 0.0, 0.0);
+                        }
+                    }
+                    MouseButton::Left => {
+                        // Clicking a third cell starts a new pair
+                        if highlighted_pair.len() == 2 {
+                            highlighted_pair.clear();
+                        }
+                        if let Some((row, col)) = view.cell_at(cursor, grid_width, grid_height) {
+                            highlighted_pair.push((row, col));
+                            // Log the full state of the clicked cell for inspection
+                            let cell = &self.grid.cells[row][col];
+                            let [one, minus_one, i, minus_i] = cell.state_probabilities;
+                            let partner = |partner: Option<(usize, usize)>| match partner {
+                                Some((row, col)) => format!("({}, {})", col, row),
+                                None => "none".to_string(),
+                            };
+                            info!(
+                                "Cell ({}, {}) at step {}: 1={:.4} -1={:.4} i={:.4} -i={:.4}, partners {} {}, time dilation {:.3}{}",
+                                col,
+                                row,
+                                sim_step,
+                                one,
+                                minus_one,
+                                i,
+                                minus_i,
+                                partner(cell.entangled_partner),
+                                partner(cell.second_partner),
+                                cell.time_dilation,
+                                if self.grid.obstacles[row][col] { ", obstacle" } else { "" }
+                            );
+                        }
+                    }
+                    MouseButton::Right => highlighted_pair.clear(),
+                    MouseButton::Middle => dragging = true,
+                    _ => {}
+                }
+            }
+
+            if let Some(pinned) = painting {
+                if let Some((row, col)) = view.cell_at(cursor, grid_width, grid_height) {
+                    self.grid.obstacles[row][col] = pinned;
+                    self.grid.wake_all();
+                }
+            }
+
+            if let Some(Button::Mouse(MouseButton::Middle)) = e.release_args() {
+                dragging = false;
+            }
+
+            match e.release_args() {
+                Some(Button::Keyboard(Key::LShift | Key::RShift)) => slow_motion = false,
+                Some(Button::Keyboard(Key::LCtrl | Key::RCtrl)) => ctrl_held = false,
+                Some(Button::Keyboard(Key::LAlt | Key::RAlt)) => alt_held = false,
+                Some(Button::Mouse(MouseButton::Left | MouseButton::Right)) => painting = None,
+                _ => {}
+            }
+
+            if let Some(Button::Keyboard(Key::C)) = e.press_args() {
+                // Rendered at the screenshot cell size rather than the window's
+                let shot_view = View {
+                    cell_size: self.config.screenshot_cell_size.max(1),
+                    ..view
+                };
+                let (width, height, pixels) = frame_rgba(
+                    &self.grid,
+                    &initial_cells,
+                    &shot_view,
+                    render_mode,
+                    &tone,
+                    colormap,
+                );
+                let path = screenshot_path();
+                match io::save_png(&path, width, height, &pixels) {
+                    Ok(()) => info!("Saved {}x{} screenshot to {}", width, height, path),
+                    Err(err) => error!("Failed to write {}: {}", path, err),
+                }
+            }
+
+            if let Some(Button::Keyboard(Key::F3)) = e.press_args() {
+                let (width, height, pixels) = frame_rgba(
+                    &self.grid,
+                    &initial_cells,
+                    &view,
+                    render_mode,
+                    &tone,
+                    colormap,
+                );
+                copy_frame(width, height, &pixels);
+            }
+
+            #[cfg(feature = "exr")]
+            if let Some(Button::Keyboard(Key::X)) = e.press_args() {
+                match io::save_exr("grid.exr", &self.grid.cells, grid_width, grid_height) {
+                    Ok(()) => info!("Saved grid probabilities to grid.exr"),
+                    Err(err) => error!("Failed to write grid.exr: {}", err),
+                }
+            }
+
+            if follow {
+                // Re-aim once a second at the activity since the last aim, and ease toward
+                // it every frame so the camera glides instead of jumping
+                if last_follow.elapsed() >= Duration::from_secs(1) {
+                    if let Some(target) = self.grid.activity_centroid(&follow_baseline) {
+                        follow_target = Some(target);
+                    }
+                    follow_baseline = self.grid.cells.clone();
+                    last_follow = Instant::now();
+                }
+                if let (Some(target), Some(_)) = (follow_target, e.render_args()) {
+                    view.follow(target, 0.05, grid_width, grid_height, window_size);
+                }
+            }
+
+            view.clamp_pan(grid_width, grid_height, window_size);
+
+            let update_interval = Duration::from_secs_f64(1.0 / steps_per_second);
+            let effective_interval = if slow_motion {
+                update_interval * slow_motion_factor
+            } else {
+                update_interval
+            };
+
+            // Looking back through the history pauses evolution
+            if !history.is_rewound() && last_update.elapsed() >= effective_interval {
+                history.record(sim_step, self.grid.steps, &self.grid.cells);
+                let update_start = Instant::now();
+                self.grid.update(); // Update the grid
+                title_stats.record_update(update_start.elapsed());
+                sim_step += 1;
+                last_update = Instant::now();
+
+                if !diverged && self.grid.has_diverged() {
+                    diverged = true;
+                    warn!(
+                        "Simulation diverged at step {}: cell probabilities are no longer finite",
+                        sim_step
+                    );
+                }
+            }
+
+            if self.state_histogram && last_histogram.elapsed() >= Duration::from_secs(1) {
+                let counts = self.grid.calculate_state_distribution();
+                info!(
+                    "Dominant states: 1 {}, -1 {}, i {}, -i {}, undecided {}",
+                    counts.one,
+                    counts.minus_one,
+                    counts.complex_i,
+                    counts.complex_minus_i,
+                    counts.undecided
+                );
+                last_histogram = Instant::now();
+            }
+
+            if let (Some(stats), Some(_)) = (frame_stats.as_mut(), e.render_args()) {
+                stats.record_frame();
+            }
+            if e.render_args().is_some() {
+                if let Some(title) = title_stats.record_frame() {
+                    window.set_title(title);
+                }
+            }
+
+            window.draw_2d(&e, |c, g, _| {
+                clear([1.0; 4], g); // Clear the screen
+                for i in 0..grid_height {
+                    for j in 0..grid_width {
+                        let state = &self.grid.cells[i][j];
+                        let color = render_mode.cell_color(
+                            &self.grid,
+                            &initial_cells,
+                            &tone,
+                            colormap,
+                            i,
+                            j,
+                        );
+                        let (x, y) = view.cell_position(i, j, grid_width, grid_height);
+                        let size = view.cell_pixels();
+                        rectangle(color, rectangle::square(x, y, size), c.transform, g); // Draw the rectangle
+                        if grid_lines {
+                            Rectangle::new_border([0.0, 0.0, 0.0, 0.35], 0.5).draw(
+                                rectangle::square(x, y, size),
+                                &c.draw_state,
+                                c.transform,
+                                g,
+                            );
+                        }
+
+                        if render_mode == RenderMode::Contours {
+                            // Draw an edge wherever the field crosses into a different level band
+                            let contour_color = [1.0, 0.9, 0.2, 1.0];
+                            let level = contour_level(state.state_probabilities[0], contour_levels);
+                            // Neighbors wrap like the grid does, so a seam panned on-screen still
+                            // gets its lines
+                            let right =
+                                self.grid.cells[i][(j + 1) % grid_width].state_probabilities[0];
+                            if contour_level(right, contour_levels) != level {
+                                line(
+                                    contour_color,
+                                    0.5,
+                                    [x + size, y, x + size, y + size],
+                                    c.transform,
+                                    g,
+                                );
+                            }
+                            let below =
+                                self.grid.cells[(i + 1) % grid_height][j].state_probabilities[0];
+                            if contour_level(below, contour_levels) != level {
+                                // With a flipped axis the next row is drawn above this one
+                                let edge_y = if view.flip_y { y } else { y + size };
+                                line(
+                                    contour_color,
+                                    0.5,
+                                    [x, edge_y, x + size, edge_y],
+                                    c.transform,
+                                    g,
+                                );
+                            }
+                        }
+                    }
+                }
+
+                // Highlighted cells are drawn on top regardless of their state
+                let highlight_color = [1.0, 0.2, 1.0, 1.0];
+                let size = view.cell_pixels();
+                let centers: Vec<(f64, f64)> = highlighted_pair
+                    .iter()
+                    .map(|&(i, j)| {
+                        let (x, y) = view.cell_position(i, j, grid_width, grid_height);
+                        rectangle(
+                            highlight_color,
+                            rectangle::square(x, y, size),
+                            c.transform,
+                            g,
+                        );
+                        (x + size / 2.0, y + size / 2.0)
+                    })
+                    .collect();
+                if let [(x1, y1), (x2, y2)] = centers[..] {
+                    line(highlight_color, 1.0, [x1, y1, x2, y2], c.transform, g);
+                }
+
+                if step_marker {
+                    // Corner square that changes color on every evolution step, so recordings
+                    // show exactly which frames an update happened on
+                    let marker_colors = [
+                        [1.0, 0.0, 0.0, 1.0],
+                        [0.0, 1.0, 0.0, 1.0],
+                        [0.0, 0.0, 1.0, 1.0],
+                        [1.0, 1.0, 1.0, 1.0],
+                    ];
+                    let marker_size = 12.0;
+                    let marker_x = window_size[0] - marker_size;
+                    rectangle(
+                        marker_colors[sim_step % marker_colors.len()],
+                        rectangle::square(marker_x, 0.0, marker_size),
+                        c.transform,
+                        g,
+                    );
+                }
+
+                if diverged {
+                    // Red frame so a blown-up grid doesn't just look like a blank screen
+                    let window_rect = [0.0, 0.0, window_size[0], window_size[1]];
+                    Rectangle::new_border([1.0, 0.0, 0.0, 1.0], 3.0).draw(
+                        window_rect,
+                        &c.draw_state,
+                        c.transform,
+                        g,
+                    );
+                }
+            });
+        }
+
+        if let Some(stats) = &frame_stats {
+            stats.print_report();
+        }
+    }
+}
+
+// Evolves a grid built from the config for `steps` updates without opening a window
+// and returns the final cells, for scripted experiments and checks on the end state
+pub fn run_headless(config: &Config, steps: u32) -> Result<Vec<Vec<CellState>>, String> {
+    config.validate()?;
+    let mut grid = Grid::with_entanglement(
+        config.grid_width,
+        config.grid_height,
+        InitDistribution::Uniform,
+        config.entanglement(),
+        None,
+    )?;
+    grid.apply_config(config);
+    grid.evolve(steps);
+    Ok(grid.cells)
+}
+
+impl Default for App {
+    fn default() -> App {
+        App::new(Config::default()).expect("the default config is valid")
+    }
+}
+
+// Copies a frame to the system clipboard, or saves it to frame.ppm when the
+// clipboard can't take images (or the `clipboard` feature is disabled)
+fn copy_frame(width: usize, height: usize, pixels: &[u8]) {
+    #[cfg(feature = "clipboard")]
+    match clipboard::copy_image(width, height, pixels) {
+        Ok(()) => {
+            info!("Copied {}x{} frame to the clipboard", width, height);
+            return;
+        }
+        Err(err) => error!(
+            "Clipboard unavailable ({}), saving to frame.ppm instead",
+            err
+        ),
+    }
+
+    match io::save_ppm("frame.ppm", width, height, pixels) {
+        Ok(()) => info!("Saved {}x{} frame to frame.ppm", width, height),
+        Err(err) => error!("Failed to write frame.ppm: {}", err),
+    }
+}
+
+// Screenshot file name stamped with the current time, so repeated captures don't overwrite
+fn screenshot_path() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    format!("screenshot-{}.png", millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_grid_refuses_sizes_the_config_rejects() {
+        let config = Config {
+            grid_width: 4096,
+            grid_height: 4096,
+            ..Config::default()
+        };
+        let mut app = App::with_grid(Grid::new_seeded(8, 8, 1).unwrap(), config).unwrap();
+        assert!(app.resize_grid(8192, 8192).is_err());
+        assert!(app.resize_grid(0, 4).is_err());
+        assert_eq!((app.grid.width, app.config.grid_width), (8, 8));
+
+        app.resize_grid(16, 12).unwrap();
+        assert_eq!((app.grid.width, app.grid.height), (16, 12));
+        assert_eq!((app.config.grid_width, app.config.grid_height), (16, 12));
+    }
+
+    #[test]
+    fn entry_points_reject_invalid_configs() {
+        let bad = [
+            Config {
+                cell_size: 0,
+                ..Config::default()
+            },
+            Config {
+                msaa_samples: 3,
+                ..Config::default()
+            },
+            Config {
+                grid_width: 8192,
+                grid_height: 8192,
+                ..Config::default()
+            },
+        ];
+        for config in bad {
+            assert!(App::new(config.clone()).is_err());
+            assert!(run_headless(&config, 1).is_err());
+        }
+        let grid = Grid::new_seeded(8, 8, 1).unwrap();
+        let config = Config {
+            msaa_samples: 3,
+            ..Config::default()
+        };
+        assert!(App::with_grid(grid, config).is_err());
+        assert!(App::new(Config::default()).is_ok());
+    }
+
+    #[test]
+    fn run_headless_evolves_a_grid_of_the_configured_size() {
+        let config = Config {
+            grid_width: 24,
+            grid_height: 10,
+            randomness_factor: 0.0,
+            collapse_strength: 1.0,
+            ..Config::default()
+        };
+        let cells = run_headless(&config, 5).unwrap();
+        assert_eq!(cells.len(), 10);
+        assert!(cells.iter().all(|row| row.len() == 24));
+        // Without noise, full collapse leaves every cell certain of one state
+        for cell in cells.iter().flatten() {
+            let total: f64 = cell.state_probabilities.iter().sum();
+            assert!((total - 1.0).abs() < 1e-9);
+            assert!(cell.state_probabilities.iter().any(|&p| p > 1.0 - 1e-9));
+        }
+    }
+}
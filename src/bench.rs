@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use crate::grid::Grid;
+
+// Throughput measured for one grid size in a benchmark run
+pub struct BenchResult {
+    width: usize,
+    height: usize,
+    steps: usize,
+    total: Duration,
+}
+
+impl BenchResult {
+    fn steps_per_sec(&self) -> f64 {
+        self.steps as f64 / self.total.as_secs_f64()
+    }
+
+    fn cells_per_sec(&self) -> f64 {
+        self.steps_per_sec() * (self.width * self.height) as f64
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"width\": {}, \"height\": {}, \"steps\": {}, \"total_ms\": {:.3}, \"steps_per_sec\": {:.3}, \"cells_per_sec\": {:.1}}}",
+            self.width,
+            self.height,
+            self.steps,
+            self.total.as_secs_f64() * 1000.0,
+            self.steps_per_sec(),
+            self.cells_per_sec()
+        )
+    }
+}
+
+const BENCH_SEED: u64 = 0x5eed;
+
+// Runs the update loop without a window over increasing grid sizes and prints how it scales
+pub fn run_bench_sweep() -> Vec<BenchResult> {
+    let sizes = [128, 256, 512, 1024, 2048];
+    let budget = Duration::from_secs(2); // Wall-clock time spent on each size
+
+    println!(
+        "{:>10} {:>8} {:>12} {:>14}",
+        "grid", "steps", "steps/sec", "cells/sec"
+    );
+    let mut results = Vec::new();
+    for &size in &sizes {
+        // A fixed seed evolves the same cells on every run, so numbers are comparable
+        let mut grid = Grid::new_seeded(size, size, BENCH_SEED)
+            .unwrap_or_else(|e| panic!("Failed to create grid: {}", e));
+        let start = Instant::now();
+        let mut steps = 0;
+        while start.elapsed() < budget {
+            grid.update();
+            steps += 1;
+        }
+
+        let result = BenchResult {
+            width: size,
+            height: size,
+            steps,
+            total: start.elapsed(),
+        };
+        println!(
+            "{:>10} {:>8} {:>12.2} {:>14.0}",
+            format!("{}x{}", size, size),
+            result.steps,
+            result.steps_per_sec(),
+            result.cells_per_sec()
+        );
+        results.push(result);
+    }
+
+    results
+}
+
+// Writes benchmark results as a JSON report that scripts can diff between commits
+pub fn write_bench_json(path: &str, results: &[BenchResult]) -> std::io::Result<()> {
+    let entries: Vec<String> = results
+        .iter()
+        .map(|result| format!("    {}", result.to_json()))
+        .collect();
+    let report = format!(
+        "{{\n  \"threads\": {},\n  \"results\": [\n{}\n  ]\n}}\n",
+        rayon::current_num_threads(),
+        entries.join(",\n")
+    );
+    std::fs::write(path, report)
+}
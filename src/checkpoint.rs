@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use crate::grid::CellState;
+use crate::io;
+
+// A manually saved grid state, kept in memory or spilled to a temporary file
+enum Checkpoint {
+    InMemory(Vec<Vec<CellState>>),
+    OnDisk(PathBuf),
+}
+
+// Stack of checkpoints pushed and popped from the keyboard, so a perturbation can be
+// tried and then undone back to a chosen state. Once the in-memory checkpoints
+// exceed the memory budget, the oldest ones are written to the temp directory.
+pub(crate) struct Checkpoints {
+    stack: Vec<Checkpoint>,
+    memory_budget: usize, // Bytes of cell data kept in memory
+    files_written: usize,
+}
+
+impl Checkpoints {
+    pub(crate) fn new(memory_budget: usize) -> Checkpoints {
+        Checkpoints {
+            stack: Vec::new(),
+            memory_budget,
+            files_written: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, cells: &[Vec<CellState>]) -> std::io::Result<()> {
+        self.stack.push(Checkpoint::InMemory(cells.to_vec()));
+
+        let cell_count: usize = cells.iter().map(|row| row.len()).sum();
+        let checkpoint_size = cell_count * std::mem::size_of::<CellState>();
+        let mut in_memory = self
+            .stack
+            .iter()
+            .filter(|checkpoint| matches!(checkpoint, Checkpoint::InMemory(_)))
+            .count();
+        for index in 0..self.stack.len() {
+            if in_memory * checkpoint_size <= self.memory_budget {
+                break;
+            }
+            if let Checkpoint::InMemory(cells) = &self.stack[index] {
+                let path = std::env::temp_dir().join(format!(
+                    "quantum-life-{}-{}.ckpt",
+                    std::process::id(),
+                    self.files_written
+                ));
+                io::save_checkpoint(&path, cells)?;
+                self.files_written += 1;
+                self.stack[index] = Checkpoint::OnDisk(path);
+                in_memory -= 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Removes and returns the most recent checkpoint, or None if the stack is empty
+    pub(crate) fn pop(&mut self) -> Option<std::io::Result<Vec<Vec<CellState>>>> {
+        match self.stack.pop()? {
+            Checkpoint::InMemory(cells) => Some(Ok(cells)),
+            Checkpoint::OnDisk(path) => {
+                let cells = io::load_checkpoint(&path);
+                let _ = std::fs::remove_file(&path);
+                Some(cells)
+            }
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.stack.len()
+    }
+}
+
+impl Drop for Checkpoints {
+    fn drop(&mut self) {
+        for checkpoint in &self.stack {
+            if let Checkpoint::OnDisk(path) = checkpoint {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use std::borrow::Cow;
+
+use arboard::{Clipboard, ImageData};
+
+// Puts tightly packed RGBA8 pixels on the system clipboard as an image
+pub fn copy_image(width: usize, height: usize, rgba: &[u8]) -> Result<(), arboard::Error> {
+    let mut clipboard = Clipboard::new()?;
+    clipboard.set_image(ImageData {
+        width,
+        height,
+        bytes: Cow::Borrowed(rgba),
+    })
+}
@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{Entanglement, DEFAULT_ENTANGLEMENT_PROBABILITY};
+
+// Largest grid side accepted. Long, thin grids may use all of it; MAX_GRID_CELLS
+// bounds the area.
+pub const MAX_GRID_DIM: usize = u16::MAX as usize;
+// Most cells accepted, 4096x4096. At about 180 bytes per cell for the cells, the
+// update snapshot and the obstacle mask, this is some 3 GB. The window's rewind
+// history is capped separately by bytes, so history_depth doesn't multiply it.
+pub const MAX_GRID_CELLS: usize = 1 << 24;
+
+// Simulation and window settings that can be changed without recompiling. Fields
+// missing from a config file keep their default values; unknown ones, such as a
+// misspelled key, are an error rather than silently ignored.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub grid_width: usize,
+    pub grid_height: usize,
+    pub cell_size: usize,            // Size of each cell in pixels
+    pub screenshot_cell_size: usize, // Size of each cell in pixels in PNG screenshots
+    pub update_interval_ms: u64,     // Time between evolution steps
+    pub max_fps: u64,                // Frame rate cap for the window; 0 leaves it uncapped
+    pub vsync: bool,                 // Sync buffer swaps to the display refresh
+    pub msaa_samples: u8,            // Multisampling for smoother lines: 0 (off), 2, 4 or 8
+    pub slow_motion_factor: u32,     // Update interval multiplier while Shift is held
+    pub randomness_factor: f64,      // Scale of the noise added to every cell each step
+    pub collapse_strength: f64,      // In [0, 1]; pull toward the dominant state after each step
+    pub voting_strength: f64,        // In [0, 1]; weight of the neighbor voting rule
+    pub survival_min: usize,         // Fewest neighbors sharing a cell's state for it to survive
+    pub survival_max: usize,         // Most neighbors sharing a cell's state for it to survive
+    pub birth_count: usize,          // Neighbors in a state needed to grow it in a cell
+    pub entanglement_coupling: f64,  // In [0, 1]; pull toward entangled partners each step
+    pub partner_probability: f64,    // In [0, 1]; chance of each partner slot in new cells
+    pub second_partner: bool,        // Give new cells a second partner slot as well
+    pub bistability_strength: f64,   // In [0, 1]; push cells away from superposition each step
+    pub hysteresis: f64,             // In [0, 1]; bonus to the state a cell was dominant in
+    pub time_dilation: bool,         // Slow high-entropy cells, down to a tenth of the rate
+    pub history_depth: usize,        // Recent grid states kept for rewinding; 0 disables it
+    pub history_interval: usize,     // Evolution steps between rewind snapshots
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            grid_width: 100,
+            grid_height: 100,
+            cell_size: 7,
+            screenshot_cell_size: 16,
+            update_interval_ms: 300, // ~ 3 times a second
+            max_fps: 120,
+            vsync: false,
+            msaa_samples: 0,
+            slow_motion_factor: 8,
+            randomness_factor: 0.01,
+            collapse_strength: 0.0,
+            voting_strength: 0.0,
+            survival_min: 2,
+            survival_max: 3,
+            birth_count: 3,
+            entanglement_coupling: 0.0,
+            partner_probability: DEFAULT_ENTANGLEMENT_PROBABILITY,
+            second_partner: false,
+            bistability_strength: 0.0,
+            hysteresis: 0.0,
+            time_dilation: false,
+            history_depth: 16,
+            history_interval: 10,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Config, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("failed to parse '{}': {}", path.display(), e))
+    }
+
+    // The config in the config-file format, ready to save as a preset
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| format!("failed to write the config: {}", e))
+    }
+
+    // How new grids draw their partners
+    pub fn entanglement(&self) -> Entanglement {
+        Entanglement {
+            probability: self.partner_probability.clamp(0.0, 1.0),
+            second_partner: self.second_partner,
+            ..Entanglement::default()
+        }
+    }
+
+    // Rejects settings the simulation cannot start with, before anything is allocated
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("width", self.grid_width), ("height", self.grid_height)] {
+            if value == 0 {
+                return Err(format!("grid {} must be at least 1", name));
+            }
+            if value > MAX_GRID_DIM {
+                return Err(format!(
+                    "grid {} {} exceeds the maximum of {}",
+                    name, value, MAX_GRID_DIM
+                ));
+            }
+        }
+        let cells = self.grid_width * self.grid_height;
+        if cells > MAX_GRID_CELLS {
+            return Err(format!(
+                "grid {}x{} has {} cells, more than the maximum of {}",
+                self.grid_width, self.grid_height, cells, MAX_GRID_CELLS
+            ));
+        }
+        if ![0, 2, 4, 8].contains(&self.msaa_samples) {
+            return Err(format!(
+                "msaa_samples must be 0, 2, 4 or 8, got {}",
+                self.msaa_samples
+            ));
+        }
+        if self.cell_size == 0 {
+            return Err("cell_size must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_grid(grid_width: usize, grid_height: usize) -> Config {
+        Config {
+            grid_width,
+            grid_height,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn validate_bounds_the_total_cells() {
+        assert!(with_grid(4096, 4096).validate().is_ok());
+        assert!(with_grid(MAX_GRID_DIM, 16).validate().is_ok());
+        assert!(with_grid(4097, 4096).validate().is_err());
+        assert!(with_grid(MAX_GRID_DIM, MAX_GRID_DIM).validate().is_err());
+        assert!(with_grid(MAX_GRID_DIM + 1, 1).validate().is_err());
+        assert!(with_grid(0, 16).validate().is_err());
+    }
+
+    #[test]
+    fn misspelled_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("grid_widht = 64\n").is_err());
+    }
+}
@@ -0,0 +1,1771 @@
+use std::f64::consts::TAU;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::imageops::{self, FilterType};
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::config::Config;
+use crate::io;
+use crate::pattern::Pattern;
+
+// Index of the most probable basic state. Ties go to the lowest index, so the
+// distribution stats and the on-screen colors always classify a cell the same way.
+pub fn dominant_state(probabilities: &[f64; 4]) -> usize {
+    let mut best = 0;
+    for (index, &prob) in probabilities.iter().enumerate().skip(1) {
+        if prob > probabilities[best] {
+            best = index;
+        }
+    }
+    best
+}
+
+// True when no state leads, as in the even [0.25; 4] background of sparse and pattern
+// grids. Such cells have no dominant state, whatever `dominant_state` returns for them.
+pub fn is_undecided(probabilities: &[f64; 4]) -> bool {
+    let max = probabilities.iter().copied().fold(f64::MIN, f64::max);
+    let min = probabilities.iter().copied().fold(f64::MAX, f64::min);
+    max - min < 1e-9
+}
+
+// How the starting probabilities of a new grid are drawn
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum InitDistribution {
+    Uniform,     // Independent random probabilities for every cell
+    Clustered,   // Random probabilities smoothed so that neighbors start out alike
+    Sparse(f64), // Undecided cells, with the given fraction strongly polarized
+}
+
+impl InitDistribution {
+    // Parses `uniform`, `clustered`, `sparse` or `sparse:<density>`
+    pub fn parse(value: &str) -> Result<InitDistribution, String> {
+        match value.split_once(':') {
+            None if value == "uniform" => Ok(InitDistribution::Uniform),
+            None if value == "clustered" => Ok(InitDistribution::Clustered),
+            None if value == "sparse" => Ok(InitDistribution::Sparse(0.02)),
+            Some(("sparse", density)) => match density.parse::<f64>() {
+                Ok(density) if (0.0..=1.0).contains(&density) => {
+                    Ok(InitDistribution::Sparse(density))
+                }
+                _ => Err(format!(
+                    "sparse density must be in [0, 1], got '{}'",
+                    density
+                )),
+            },
+            _ => Err(format!(
+                "unknown distribution '{}', expected uniform, clustered or sparse[:density]",
+                value
+            )),
+        }
+    }
+
+    fn sample_probabilities<R: Rng>(&self, rng: &mut R) -> [f64; 4] {
+        match *self {
+            InitDistribution::Uniform | InitDistribution::Clustered => {
+                // Random probabilities for each state
+                let mut probs = [0.0; 4];
+                for p in &mut probs {
+                    *p = rng.gen::<f64>();
+                }
+                let sum: f64 = probs.iter().sum();
+                for p in &mut probs {
+                    *p /= sum; // Normalize probabilities to sum to 1
+                }
+                probs
+            }
+            InitDistribution::Sparse(density) => {
+                if rng.gen::<f64>() < density {
+                    let mut probs = [0.05; 4];
+                    probs[rng.gen_range(0..4)] = 0.85;
+                    probs
+                } else {
+                    [0.25; 4]
+                }
+            }
+        }
+    }
+}
+
+// Probabilities representing the complex phase e^(i phase). The basic states are the four
+// phases 1, -1, i and -i, so the phase is written as weights on the two basic states
+// bracketing it, (cos^2, sin^2) on the matching signs, which always sum to 1.
+pub(crate) fn phase_probabilities(phase: f64) -> [f64; 4] {
+    let (re, im) = (phase.cos(), phase.sin());
+    [
+        re.max(0.0).powi(2),
+        (-re).max(0.0).powi(2),
+        im.max(0.0).powi(2),
+        (-im).max(0.0).powi(2),
+    ]
+}
+
+// A partner (row, col) for the cell at (row, col): anywhere on the grid, or within
+// `max_distance` of it in both directions, wrapping around the edges. Never the cell
+// itself, so None when no other cell is in reach.
+fn random_partner(
+    rng: &mut impl Rng,
+    row: usize,
+    col: usize,
+    width: usize,
+    height: usize,
+    max_distance: Option<usize>,
+) -> Option<(usize, usize)> {
+    if width * height == 1 || max_distance == Some(0) {
+        return None;
+    }
+    loop {
+        let partner = match max_distance {
+            None => (rng.gen_range(0..height), rng.gen_range(0..width)),
+            Some(distance) => {
+                let distance = distance as isize;
+                let dr = rng.gen_range(-distance..=distance);
+                let dc = rng.gen_range(-distance..=distance);
+                (
+                    (row as isize + dr).rem_euclid(height as isize) as usize,
+                    (col as isize + dc).rem_euclid(width as isize) as usize,
+                )
+            }
+        };
+        if partner != (row, col) {
+            return Some(partner);
+        }
+    }
+}
+
+// Shortest signed distance equivalent to `offset` on a ring of the given size
+pub(crate) fn wrapped_offset(offset: f64, size: f64) -> f64 {
+    let offset = offset.rem_euclid(size);
+    if offset > size / 2.0 {
+        offset - size
+    } else {
+        offset
+    }
+}
+
+// Seed for one row's generator in one step, mixed from the grid seed with the
+// SplitMix64 finalizer so neighboring rows and steps get unrelated streams
+fn row_seed(seed: u64, step: u64, row: usize) -> u64 {
+    let mut z = seed
+        ^ step.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (row as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Seed for the `count`th re-randomization of a grid seeded with `seed`, so
+// re-randomizing a seeded run gives new cells that the same seed reproduces
+pub(crate) fn reseed(seed: u64, count: u64) -> u64 {
+    row_seed(seed, count, usize::MAX)
+}
+
+#[derive(Clone, Debug, Copy)]
+pub struct CellState {
+    pub state_probabilities: [f64; 4], // Probabilities for each basic state
+    pub entangled_partner: Option<(usize, usize)>, // Optional entangled partner (row, col)
+    pub second_partner: Option<(usize, usize)>, // Optional second partner (row, col)
+    pub time_dilation: f64, // Rate the cell evolves at, in [0.1, 1.0]; follows its entropy
+}
+
+// Evolution rate for a cell in the given state: 1.0 for a pure state (zero Shannon
+// entropy), falling linearly with entropy to 0.1 for the uniform mix of all four states
+pub fn time_dilation(probabilities: &[f64; 4]) -> f64 {
+    let entropy: f64 = probabilities
+        .iter()
+        .filter(|&&prob| prob > 0.0)
+        .map(|&prob| -prob * prob.ln())
+        .sum();
+    let max_entropy = 4.0_f64.ln();
+    (1.0 - 0.9 * entropy / max_entropy).clamp(0.1, 1.0)
+}
+
+impl CellState {
+    // Blends the probabilities toward certainty in the dominant state. A strength of 0
+    // leaves the cell untouched and 1 snaps it fully to that state.
+    fn collapse(&mut self, strength: f64) {
+        let dominant = dominant_state(&self.state_probabilities);
+        for (state, prob) in self.state_probabilities.iter_mut().enumerate() {
+            let target = if state == dominant { 1.0 } else { 0.0 };
+            *prob = (1.0 - strength) * *prob + strength * target;
+        }
+    }
+
+    // Pushes the probabilities away from the uniform mix by `strength`, penalizing
+    // superposition so cells settle into one state or another
+    fn sharpen(&mut self, strength: f64) {
+        for prob in &mut self.state_probabilities {
+            *prob = (*prob + strength * (*prob - 0.25)).max(0.0);
+        }
+        normalize(&mut self.state_probabilities);
+    }
+
+    // Adds `hysteresis` to the state that was dominant before this step, so committed
+    // cells resist being flipped by their neighbors
+    fn persist(&mut self, previous_dominant: usize, hysteresis: f64) {
+        self.state_probabilities[previous_dominant] += hysteresis;
+        normalize(&mut self.state_probabilities);
+    }
+
+    // Blends the probabilities toward each entangled partner's (as they were before this
+    // step) by `coupling`. Like every partner lookup, this relies on partners lying inside
+    // the grid: generated cells draw them there and `Grid::load` rejects files where they
+    // don't.
+    fn couple(&mut self, grid: &[Vec<CellState>], coupling: f64) {
+        for (row, col) in [self.entangled_partner, self.second_partner]
+            .into_iter()
+            .flatten()
+        {
+            let partner = &grid[row][col];
+            for (prob, target) in self
+                .state_probabilities
+                .iter_mut()
+                .zip(partner.state_probabilities)
+            {
+                *prob += coupling * (target - *prob);
+            }
+        }
+    }
+
+    // Life-like majority voting on dominant states. The cell's dominant state is reinforced
+    // when the number of neighbors sharing it lies within the survival range and weakened
+    // otherwise; any other state held by exactly `birth_count` neighbors is reinforced.
+    fn vote(&mut self, neighbors: &[CellState], rules: &VotingRules) {
+        let mut counts = [0; 4];
+        // Undecided neighbors, including empty ones past a fixed boundary, have no
+        // dominant state
+        for neighbor in neighbors
+            .iter()
+            .filter(|neighbor| !is_undecided(&neighbor.state_probabilities))
+        {
+            counts[dominant_state(&neighbor.state_probabilities)] += 1;
+        }
+
+        let dominant = if is_undecided(&self.state_probabilities) {
+            None
+        } else {
+            Some(dominant_state(&self.state_probabilities))
+        };
+        for (state, prob) in self.state_probabilities.iter_mut().enumerate() {
+            let reinforce = if Some(state) == dominant {
+                (rules.survival_min..=rules.survival_max).contains(&counts[state])
+            } else if counts[state] == rules.birth_count {
+                true
+            } else {
+                continue;
+            };
+            if reinforce {
+                *prob += rules.strength * (1.0 - *prob);
+            } else {
+                *prob -= rules.strength * *prob;
+            }
+        }
+
+        normalize(&mut self.state_probabilities);
+    }
+}
+
+// Scales the probabilities to sum to 1, leaving an all-zero cell as it is
+fn normalize(probabilities: &mut [f64; 4]) {
+    let total: f64 = probabilities.iter().sum();
+    if total > 0.0 {
+        for prob in probabilities {
+            *prob /= total;
+        }
+    }
+}
+
+// Chance that each partner slot of a freshly generated cell is filled
+pub const DEFAULT_ENTANGLEMENT_PROBABILITY: f64 = 0.88;
+
+// How partners are drawn when cells are generated, kept so that resizing and
+// re-randomizing draw them the same way
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub struct Entanglement {
+    pub probability: f64,            // In [0, 1]; 0 leaves every cell unentangled
+    pub max_distance: Option<usize>, // Toroidal Chebyshev bound on partners; None for anywhere
+    pub second_partner: bool,        // Also draw a second partner for each cell
+}
+
+impl Default for Entanglement {
+    fn default() -> Entanglement {
+        Entanglement {
+            probability: DEFAULT_ENTANGLEMENT_PROBABILITY,
+            max_distance: None,
+            second_partner: false,
+        }
+    }
+}
+
+// Neighbor counts for the voting rule, B3/S23 by default as in Conway's Life
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub struct VotingRules {
+    pub strength: f64, // In [0, 1]; 0 disables voting
+    pub survival_min: usize,
+    pub survival_max: usize,
+    pub birth_count: usize,
+}
+
+impl Grid {
+    pub fn calculate_state_distribution(&self) -> StateDistribution {
+        let mut distribution = StateDistribution {
+            one: 0,
+            minus_one: 0,
+            complex_i: 0,
+            complex_minus_i: 0,
+            undecided: 0,
+        };
+
+        for row in &self.cells {
+            for cell in row {
+                if is_undecided(&cell.state_probabilities) {
+                    distribution.undecided += 1;
+                    continue;
+                }
+                match dominant_state(&cell.state_probabilities) {
+                    0 => distribution.one += 1,
+                    1 => distribution.minus_one += 1,
+                    2 => distribution.complex_i += 1,
+                    _ => distribution.complex_minus_i += 1,
+                }
+            }
+        }
+
+        distribution
+    }
+
+    // Total variation distance between cell (i, j) now and in `initial`, in [0, 1]
+    pub fn drift_from(&self, initial: &[Vec<CellState>], i: usize, j: usize) -> f64 {
+        self.cells[i][j]
+            .state_probabilities
+            .iter()
+            .zip(initial[i][j].state_probabilities.iter())
+            .map(|(p, q)| (p - q).abs())
+            .sum::<f64>()
+            / 2.0
+    }
+
+    // Center (column, row) of the cells that changed most since `previous`, weighted by
+    // how far each drifted. Uses circular means so activity across a wrapped edge stays
+    // together. None if nothing changed.
+    pub fn activity_centroid(&self, previous: &[Vec<CellState>]) -> Option<(f64, f64)> {
+        let (mut x_cos, mut x_sin, mut y_cos, mut y_sin, mut total) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for i in 0..self.height {
+            let y_angle = i as f64 / self.height as f64 * TAU;
+            for j in 0..self.width {
+                let weight = self.drift_from(previous, i, j);
+                let x_angle = j as f64 / self.width as f64 * TAU;
+                x_cos += weight * x_angle.cos();
+                x_sin += weight * x_angle.sin();
+                y_cos += weight * y_angle.cos();
+                y_sin += weight * y_angle.sin();
+                total += weight;
+            }
+        }
+        if total < 1e-9 {
+            return None;
+        }
+        let col = (x_sin.atan2(x_cos) / TAU * self.width as f64).rem_euclid(self.width as f64);
+        let row = (y_sin.atan2(y_cos) / TAU * self.height as f64).rem_euclid(self.height as f64);
+        Some((col, row))
+    }
+
+    fn calculate_new_state(
+        grid: &[Vec<CellState>],
+        current_state: &CellState,
+        neighbors: &[CellState],
+        randomness_factor: f64,
+        rng: &mut impl Rng,
+    ) -> CellState {
+        let mut new_state = *current_state;
+
+        // Entanglement Logic - enhanced for more structured behavior
+        let partners = [
+            current_state.entangled_partner,
+            current_state.second_partner,
+        ];
+        for (partner_row, partner_col) in partners.into_iter().flatten() {
+            let partner_state = &grid[partner_row][partner_col];
+
+            // Example: Synchronize states if certain conditions are met
+            for i in 0..4 {
+                if rng.gen::<f64>() < randomness_factor {
+                    new_state.state_probabilities[i] = (new_state.state_probabilities[i]
+                        + partner_state.state_probabilities[i])
+                        / 2.0;
+                }
+            }
+        }
+
+        // Calculate the weighted influence of neighbors
+        let mut neighbor_influence = [0.0; 4];
+        for neighbor in neighbors {
+            for (i, &prob) in neighbor.state_probabilities.iter().enumerate() {
+                neighbor_influence[i] += prob;
+            }
+        }
+
+        // Normalize the influence
+        let total_influence: f64 = neighbor_influence.iter().sum();
+        if total_influence > 0.0 {
+            for influence in &mut neighbor_influence {
+                *influence /= total_influence;
+            }
+        }
+
+        // Update state probabilities based on neighbor influence and some randomness
+        for (i, prob) in new_state.state_probabilities.iter_mut().enumerate() {
+            *prob = (*prob + neighbor_influence[i]) / 2.0;
+            *prob += rng.gen::<f64>() * randomness_factor; // Reduced randomness
+        }
+
+        // Ensure probabilities sum to 1
+        let total_prob: f64 = new_state.state_probabilities.iter().sum();
+        for prob in &mut new_state.state_probabilities {
+            *prob /= total_prob;
+        }
+
+        new_state
+    }
+}
+
+// Side length in cells of the tiles tracked by the sparse update
+const ACTIVITY_TILE: usize = 16;
+// Largest per-step probability change that still counts as quiet. With the default
+// randomness factor every update adds up to 0.01 of noise before renormalizing, so this
+// sits just above the noise floor.
+const ACTIVITY_THRESHOLD: f64 = 0.01;
+
+pub struct Grid {
+    pub cells: Vec<Vec<CellState>>,
+    pub width: usize,
+    pub height: usize,
+    pub stencil: Stencil,
+    pub boundary: Boundary,
+    pub sources: Vec<DrivenSource>, // Cells forced to oscillate every step
+    pub obstacles: Vec<Vec<bool>>,  // Pinned cells that hold their state and never evolve
+    active_tiles: Option<Vec<bool>>, // Tiles evolved by the next update; None updates every cell
+    pub randomness_factor: f64,     // Scale of the noise added to every cell each step
+    pub collapse_strength: f64,     // How far each step pulls cells toward their dominant state
+    pub voting: VotingRules,
+    pub entanglement_coupling: f64, // In [0, 1]; pull toward the partners' states each step
+    pub bistability: f64,           // In [0, 1]; push away from superposition each step
+    pub hysteresis: f64,            // In [0, 1]; bonus to the state that was dominant last step
+    pub dilate_time: bool,          // Scale each cell's step by its time dilation
+    pub entanglement: Entanglement, // How partners are drawn when the grid is regenerated
+    pub seed: Option<u64>,          // Seed of the update's random draws; None draws from entropy
+    pub steps: u64,                 // Updates applied since the grid was created
+}
+
+// A cell driven as a continuous wave source: every step its phase advances by
+// `frequency` radians and its state is blended toward that phase by `amplitude`
+#[derive(Clone, Debug, Copy)]
+pub struct DrivenSource {
+    pub row: usize,
+    pub col: usize,
+    pub amplitude: f64, // In [0, 1]; 0 leaves the cell undecided
+    pub frequency: f64, // Radians per step
+    pub phase: f64,     // Radians at step 0
+}
+
+impl DrivenSource {
+    // Parses `x,y,freq,amp` as given to `--source`
+    pub fn parse(value: &str) -> Result<DrivenSource, String> {
+        let fields: Vec<&str> = value.split(',').collect();
+        if fields.len() != 4 {
+            return Err(format!("expected x,y,freq,amp, got '{}'", value));
+        }
+        let number = |field: &str| match field.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            _ => Err(format!("invalid number '{}' in '{}'", field, value)),
+        };
+        let position = |field: &str| {
+            field.trim().parse::<usize>().map_err(|_| {
+                format!(
+                    "source position must be a whole number of cells, got '{}' in '{}'",
+                    field, value
+                )
+            })
+        };
+
+        Ok(DrivenSource {
+            row: position(fields[1])?,
+            col: position(fields[0])?,
+            amplitude: number(fields[3])?.clamp(0.0, 1.0),
+            frequency: number(fields[2])?,
+            phase: 0.0,
+        })
+    }
+}
+
+// Which surrounding cells count as a cell's neighbors
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum Stencil {
+    VonNeumann, // The 4 orthogonally adjacent cells
+    Moore,      // All 8 adjacent cells
+    Extended,   // The Moore cells plus the 4 cells two steps away along each axis
+}
+
+impl Stencil {
+    // (row, col) offsets of the neighbors relative to the cell
+    fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Stencil::VonNeumann => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Stencil::Moore => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+            Stencil::Extended => &[
+                (-2, 0),
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -2),
+                (0, -1),
+                (0, 1),
+                (0, 2),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+                (2, 0),
+            ],
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Stencil, String> {
+        match value {
+            "von-neumann" => Ok(Stencil::VonNeumann),
+            "moore" => Ok(Stencil::Moore),
+            "extended" => Ok(Stencil::Extended),
+            _ => Err(format!(
+                "unknown stencil '{}', expected von-neumann, moore or extended",
+                value
+            )),
+        }
+    }
+
+    pub fn next(self) -> Stencil {
+        match self {
+            Stencil::VonNeumann => Stencil::Moore,
+            Stencil::Moore => Stencil::Extended,
+            Stencil::Extended => Stencil::VonNeumann,
+        }
+    }
+}
+
+// What neighbors past the grid edges are
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum Boundary {
+    Toroidal,   // Edges wrap around to the opposite side
+    Reflecting, // Out-of-range neighbors are the nearest edge cell
+    Fixed,      // Out-of-range neighbors hold zero probability in every state
+}
+
+impl Boundary {
+    pub fn parse(value: &str) -> Result<Boundary, String> {
+        match value {
+            "toroidal" => Ok(Boundary::Toroidal),
+            "reflecting" => Ok(Boundary::Reflecting),
+            "fixed" => Ok(Boundary::Fixed),
+            _ => Err(format!(
+                "unknown boundary '{}', expected toroidal, reflecting or fixed",
+                value
+            )),
+        }
+    }
+
+    pub fn next(self) -> Boundary {
+        match self {
+            Boundary::Toroidal => Boundary::Reflecting,
+            Boundary::Reflecting => Boundary::Fixed,
+            Boundary::Fixed => Boundary::Toroidal,
+        }
+    }
+}
+
+// Bytes held by a grid's buffers, for sizing --grid against available memory
+pub struct MemoryUsage {
+    pub per_cell: usize,
+    pub cells: usize,     // The live cell buffer
+    pub snapshot: usize,  // Copy of the cells that each update reads neighbors from
+    pub obstacles: usize, // Obstacle mask
+    pub sources: usize,   // Driven sources
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.cells + self.snapshot + self.obstacles + self.sources
+    }
+}
+
+// A struct to hold counts of different types of cell states for the entire grid
+#[derive(Clone, Debug, Copy)]
+pub struct StateDistribution {
+    pub one: usize,
+    pub minus_one: usize,
+    pub complex_i: usize,
+    pub complex_minus_i: usize,
+    pub undecided: usize, // Cells with no leading state, counted apart from the four above
+}
+
+impl Grid {
+    // A uniformly random grid where each partner slot is filled with the given probability
+    pub fn new(width: usize, height: usize, entanglement_probability: f64) -> Result<Grid, String> {
+        let entanglement = Entanglement {
+            probability: entanglement_probability,
+            ..Entanglement::default()
+        };
+        Grid::with_entanglement(width, height, InitDistribution::Uniform, entanglement, None)
+    }
+
+    pub fn new_default(width: usize, height: usize) -> Result<Grid, String> {
+        Grid::new(width, height, DEFAULT_ENTANGLEMENT_PROBABILITY)
+    }
+
+    pub fn new_with(
+        width: usize,
+        height: usize,
+        distribution: InitDistribution,
+    ) -> Result<Grid, String> {
+        Grid::with_entanglement(width, height, distribution, Entanglement::default(), None)
+    }
+
+    // Like `new_default`, but partners are drawn from within a toroidal Chebyshev distance
+    // of `max_distance`, so entanglement is local instead of spread over the whole grid
+    pub fn new_with_local_entanglement(
+        width: usize,
+        height: usize,
+        max_distance: usize,
+    ) -> Result<Grid, String> {
+        let entanglement = Entanglement {
+            max_distance: Some(max_distance),
+            ..Entanglement::default()
+        };
+        Grid::with_entanglement(width, height, InitDistribution::Uniform, entanglement, None)
+    }
+
+    // Like `new_default`, but every random draw comes from `seed`, as do the updates'
+    // random draws, so the same seed always reproduces the same run
+    pub fn new_seeded(width: usize, height: usize, seed: u64) -> Result<Grid, String> {
+        Grid::new_seeded_with(width, height, InitDistribution::Uniform, seed)
+    }
+
+    pub fn new_seeded_with(
+        width: usize,
+        height: usize,
+        distribution: InitDistribution,
+        seed: u64,
+    ) -> Result<Grid, String> {
+        Grid::with_entanglement(
+            width,
+            height,
+            distribution,
+            Entanglement::default(),
+            Some(seed),
+        )
+    }
+
+    // The general form of the constructors above: cells drawn from `distribution`,
+    // partners drawn as `entanglement` says, and every draw from `seed` if given
+    pub fn with_entanglement(
+        width: usize,
+        height: usize,
+        distribution: InitDistribution,
+        entanglement: Entanglement,
+        seed: Option<u64>,
+    ) -> Result<Grid, String> {
+        let mut grid = match seed {
+            Some(seed) => Grid::generate(
+                width,
+                height,
+                distribution,
+                entanglement,
+                &mut StdRng::seed_from_u64(seed),
+            )?,
+            None => Grid::generate(
+                width,
+                height,
+                distribution,
+                entanglement,
+                &mut rand::thread_rng(),
+            )?,
+        };
+        grid.seed = seed;
+        Ok(grid)
+    }
+
+    fn generate(
+        width: usize,
+        height: usize,
+        distribution: InitDistribution,
+        entanglement: Entanglement,
+        rng: &mut impl Rng,
+    ) -> Result<Grid, String> {
+        if width == 0 || height == 0 {
+            return Err(format!(
+                "grid dimensions must be at least 1x1, got {}x{}",
+                width, height
+            ));
+        }
+
+        let cells = (0..height)
+            .map(|row| {
+                (0..width)
+                    .map(|col| {
+                        let probs = distribution.sample_probabilities(rng);
+
+                        // Each partner slot is filled with the entanglement probability. The
+                        // second is only drawn when enabled, so grids without it take the same
+                        // random draws, seeded or not, as before it existed.
+                        let mut draw_partner = || {
+                            if rng.gen::<f64>() < entanglement.probability {
+                                random_partner(
+                                    rng,
+                                    row,
+                                    col,
+                                    width,
+                                    height,
+                                    entanglement.max_distance,
+                                )
+                            } else {
+                                None
+                            }
+                        };
+                        let entangled_partner = draw_partner();
+                        let second_partner = if entanglement.second_partner {
+                            draw_partner()
+                        } else {
+                            None
+                        };
+
+                        CellState {
+                            state_probabilities: probs,
+                            entangled_partner,
+                            second_partner,
+                            time_dilation: time_dilation(&probs),
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut grid = Grid::from_cells(cells, width, height);
+        grid.entanglement = entanglement;
+        if distribution == InitDistribution::Clustered {
+            grid.smooth_probabilities(3);
+        }
+
+        Ok(grid)
+    }
+
+    // A grid laid out from a pattern. Apart from `Pattern::Random`, every cell starts
+    // undecided and unentangled except the pattern's, which are certain 'One' cells.
+    pub fn with_pattern(width: usize, height: usize, pattern: Pattern) -> Result<Grid, String> {
+        if pattern == Pattern::Random {
+            return Grid::new_default(width, height);
+        }
+        if width == 0 || height == 0 {
+            return Err(format!(
+                "grid dimensions must be at least 1x1, got {}x{}",
+                width, height
+            ));
+        }
+
+        let background = CellState {
+            state_probabilities: [0.25; 4],
+            entangled_partner: None,
+            second_partner: None,
+            time_dilation: time_dilation(&[0.25; 4]),
+        };
+        let mut cells = vec![vec![background; width]; height];
+        for (row, col) in pattern.cells(width, height) {
+            cells[row][col].state_probabilities = [1.0, 0.0, 0.0, 0.0];
+            cells[row][col].time_dilation = 1.0;
+        }
+        Ok(Grid::from_cells(cells, width, height))
+    }
+
+    // A grid around existing cells, with the default stencil and evolution parameters
+    fn from_cells(cells: Vec<Vec<CellState>>, width: usize, height: usize) -> Grid {
+        let mut grid = Grid {
+            cells,
+            width,
+            height,
+            stencil: Stencil::Moore,
+            boundary: Boundary::Toroidal,
+            sources: Vec::new(),
+            obstacles: vec![vec![false; width]; height],
+            active_tiles: None,
+            randomness_factor: 0.0,
+            collapse_strength: 0.0,
+            voting: VotingRules {
+                strength: 0.0,
+                survival_min: 2,
+                survival_max: 3,
+                birth_count: 3,
+            },
+            entanglement_coupling: 0.0,
+            bistability: 0.0,
+            hysteresis: 0.0,
+            dilate_time: false,
+            entanglement: Entanglement::default(),
+            seed: None,
+            steps: 0,
+        };
+        grid.apply_config(&Config::default());
+        grid
+    }
+
+    // Writes the cells to a grid state file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        io::save_state(&path, &self.cells)
+            .map_err(|e| format!("failed to write '{}': {}", path.as_ref().display(), e))
+    }
+
+    // Reads a grid state file written by `save`. The stencil and evolution parameters
+    // are not part of the file and start at their defaults.
+    pub fn load(path: impl AsRef<Path>) -> Result<Grid, String> {
+        let path = path.as_ref();
+        let cells = io::load_state(path)
+            .map_err(|e| format!("failed to load '{}': {}", path.display(), e))?;
+        let height = cells.len();
+        let width = cells.first().map_or(0, |row| row.len());
+        if width == 0 || height == 0 {
+            return Err(format!("'{}' holds an empty grid", path.display()));
+        }
+
+        let out_of_bounds = cells.iter().flatten().any(|cell| {
+            [cell.entangled_partner, cell.second_partner]
+                .into_iter()
+                .flatten()
+                .any(|(row, col)| row >= height || col >= width)
+        });
+        if out_of_bounds {
+            return Err(format!(
+                "'{}' has entangled partners outside its {}x{} grid",
+                path.display(),
+                width,
+                height
+            ));
+        }
+
+        Ok(Grid::from_cells(cells, width, height))
+    }
+
+    // Seeds a grid from an image file of any size or format. The image is converted to
+    // grayscale and resampled bilinearly to the grid size, then each pixel's brightness
+    // blends its cell between a mostly 'MinusOne' (dark) and a mostly 'One' (bright)
+    // state, so dark areas stay distinct from bright ones in every view. A seed fixes the
+    // partners drawn and the updates, as for with_entanglement.
+    pub fn from_image(
+        path: &str,
+        width: usize,
+        height: usize,
+        entanglement: Entanglement,
+        seed: Option<u64>,
+    ) -> Result<Grid, String> {
+        let mut grid =
+            Grid::with_entanglement(width, height, InitDistribution::Uniform, entanglement, seed)?;
+        let image = image::open(path)
+            .map_err(|e| format!("failed to load '{}': {}", path, e))?
+            .to_luma8();
+        let resized = imageops::resize(&image, width as u32, height as u32, FilterType::Triangle);
+
+        for (i, row) in grid.cells.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                let brightness = resized.get_pixel(j as u32, i as u32)[0] as f64 / 255.0;
+                // A floor of 0.05 on every state keeps the cells in superposition
+                cell.state_probabilities = [
+                    0.05 + 0.8 * brightness,
+                    0.05 + 0.8 * (1.0 - brightness),
+                    0.05,
+                    0.05,
+                ];
+                cell.time_dilation = time_dilation(&cell.state_probabilities);
+            }
+        }
+
+        Ok(grid)
+    }
+
+    // Replaces each cell's probabilities with the average over itself and its neighbors,
+    // `passes` times, so that nearby cells start out in similar states
+    fn smooth_probabilities(&mut self, passes: usize) {
+        for _ in 0..passes {
+            let previous = self.cells.clone();
+            for (i, row) in self.cells.iter_mut().enumerate() {
+                for (j, cell) in row.iter_mut().enumerate() {
+                    let mut sum = previous[i][j].state_probabilities;
+                    for (neighbor_row, neighbor_col) in
+                        Grid::neighbor_coords(i, j, self.width, self.height, self.stencil)
+                    {
+                        let neighbor = &previous[neighbor_row][neighbor_col];
+                        for (total, prob) in sum.iter_mut().zip(neighbor.state_probabilities) {
+                            *total += prob;
+                        }
+                    }
+
+                    let total: f64 = sum.iter().sum();
+                    for (prob, summed) in cell.state_probabilities.iter_mut().zip(sum) {
+                        *prob = summed / total;
+                    }
+                    cell.time_dilation = time_dilation(&cell.state_probabilities);
+                }
+            }
+        }
+    }
+
+    // Blends a Gaussian wave packet centered on (x, y) into the grid, with the local phase
+    // of the plane wave e^(i k.r) and an envelope that sets how much of each cell it replaces
+    pub fn inject_wave_packet(&mut self, center: (f64, f64), sigma: f64, k_x: f64, k_y: f64) {
+        let (width, height) = (self.width as f64, self.height as f64);
+        self.wake_all();
+
+        for (i, row) in self.cells.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                // Offset to the nearest copy of the center on the toroidal grid
+                let dx = wrapped_offset(j as f64 - center.0, width);
+                let dy = wrapped_offset(i as f64 - center.1, height);
+                let envelope = (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp();
+                if envelope < 1e-3 {
+                    continue;
+                }
+
+                let packet = phase_probabilities(k_x * dx + k_y * dy);
+                for (prob, target) in cell.state_probabilities.iter_mut().zip(packet) {
+                    *prob = (1.0 - envelope) * *prob + envelope * target;
+                }
+                cell.time_dilation = time_dilation(&cell.state_probabilities);
+            }
+        }
+    }
+
+    // Overwrites each driven source cell with its oscillator's state at the current step
+    pub fn apply_sources(&mut self) {
+        for source in &self.sources {
+            let phase = source.frequency * self.steps as f64 + source.phase;
+            let driven = phase_probabilities(phase);
+            let cell = &mut self.cells[source.row][source.col];
+            for (prob, target) in cell.state_probabilities.iter_mut().zip(driven) {
+                *prob = (1.0 - source.amplitude) * 0.25 + source.amplitude * target;
+            }
+            cell.time_dilation = time_dilation(&cell.state_probabilities);
+        }
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let cell_count = self.width * self.height;
+        let per_cell = std::mem::size_of::<CellState>();
+        MemoryUsage {
+            per_cell,
+            cells: cell_count * per_cell,
+            snapshot: cell_count * per_cell,
+            obstacles: cell_count * std::mem::size_of::<bool>(),
+            sources: self.sources.len() * std::mem::size_of::<DrivenSource>(),
+        }
+    }
+
+    // True once any probability has become non-finite or left the [0, 1] range
+    pub fn has_diverged(&self) -> bool {
+        self.cells.iter().flatten().any(|cell| {
+            cell.state_probabilities
+                .iter()
+                .any(|&prob| !prob.is_finite() || !(-1e-9..=1.0 + 1e-9).contains(&prob))
+        })
+    }
+
+    // A new random grid of another size with the same stencil, evolution parameters and
+    // update mode. Driven sources outside the new bounds are dropped.
+    pub fn resized(
+        &self,
+        width: usize,
+        height: usize,
+        distribution: InitDistribution,
+    ) -> Result<Grid, String> {
+        let mut grid =
+            Grid::with_entanglement(width, height, distribution, self.entanglement, self.seed)?;
+        grid.stencil = self.stencil;
+        grid.boundary = self.boundary;
+        grid.randomness_factor = self.randomness_factor;
+        grid.collapse_strength = self.collapse_strength;
+        grid.voting = self.voting;
+        grid.entanglement_coupling = self.entanglement_coupling;
+        grid.bistability = self.bistability;
+        grid.hysteresis = self.hysteresis;
+        grid.dilate_time = self.dilate_time;
+        grid.sources = self
+            .sources
+            .iter()
+            .filter(|source| source.row < height && source.col < width)
+            .copied()
+            .collect();
+        if self.active_tiles.is_some() {
+            grid.enable_sparse_update();
+        }
+        Ok(grid)
+    }
+
+    // Takes the evolution parameters from a config
+    pub fn apply_config(&mut self, config: &Config) {
+        self.randomness_factor = config.randomness_factor;
+        self.collapse_strength = config.collapse_strength.clamp(0.0, 1.0);
+        self.voting = VotingRules {
+            strength: config.voting_strength.clamp(0.0, 1.0),
+            survival_min: config.survival_min,
+            survival_max: config.survival_max,
+            birth_count: config.birth_count,
+        };
+        self.entanglement_coupling = config.entanglement_coupling.clamp(0.0, 1.0);
+        self.bistability = config.bistability_strength.clamp(0.0, 1.0);
+        self.hysteresis = config.hysteresis.clamp(0.0, 1.0);
+        self.dilate_time = config.time_dilation;
+        self.entanglement.probability = config.partner_probability.clamp(0.0, 1.0);
+        self.entanglement.second_partner = config.second_partner;
+        self.wake_all();
+    }
+
+    // The reverse of apply_config: copies the grid's current size and evolution
+    // parameters, including any changed from the keyboard, into the config
+    pub fn write_config(&self, config: &mut Config) {
+        config.grid_width = self.width;
+        config.grid_height = self.height;
+        config.randomness_factor = self.randomness_factor;
+        config.collapse_strength = self.collapse_strength;
+        config.voting_strength = self.voting.strength;
+        config.survival_min = self.voting.survival_min;
+        config.survival_max = self.voting.survival_max;
+        config.birth_count = self.voting.birth_count;
+        config.entanglement_coupling = self.entanglement_coupling;
+        config.bistability_strength = self.bistability;
+        config.hysteresis = self.hysteresis;
+        config.time_dilation = self.dilate_time;
+        config.partner_probability = self.entanglement.probability;
+        config.second_partner = self.entanglement.second_partner;
+    }
+
+    // Nudges the collapse strength, keeping it within [0, 1]
+    pub fn adjust_collapse(&mut self, delta: f64) {
+        self.collapse_strength = ((self.collapse_strength + delta) * 100.0).round() / 100.0;
+        self.collapse_strength = self.collapse_strength.clamp(0.0, 1.0);
+        self.wake_all();
+        info!("Collapse strength: {:.2}", self.collapse_strength);
+    }
+
+    // Nudges the bistability strength, keeping it within [0, 1]
+    pub fn adjust_bistability(&mut self, delta: f64) {
+        self.bistability = ((self.bistability + delta) * 100.0).round() / 100.0;
+        self.bistability = self.bistability.clamp(0.0, 1.0);
+        self.wake_all();
+        info!("Bistability strength: {:.2}", self.bistability);
+    }
+
+    // Nudges the hysteresis, keeping it within [0, 1]
+    pub fn adjust_hysteresis(&mut self, delta: f64) {
+        self.hysteresis = ((self.hysteresis + delta) * 100.0).round() / 100.0;
+        self.hysteresis = self.hysteresis.clamp(0.0, 1.0);
+        self.wake_all();
+        info!("Hysteresis: {:.2}", self.hysteresis);
+    }
+
+    // Multiplies the noise scale by `factor`, within [0, 1]. Halving drops tiny values
+    // to exactly 0, and doubling 0 restarts from 0.001, so noise can be switched off and on.
+    pub fn scale_randomness(&mut self, factor: f64) {
+        self.randomness_factor = if self.randomness_factor == 0.0 && factor > 1.0 {
+            0.001
+        } else {
+            self.randomness_factor * factor
+        };
+        if self.randomness_factor < 0.0001 {
+            self.randomness_factor = 0.0;
+        }
+        self.randomness_factor = self.randomness_factor.min(1.0);
+        self.wake_all();
+        info!("Randomness factor: {}", self.randomness_factor);
+    }
+
+    pub fn update(&mut self) {
+        let width = self.width;
+        let height = self.height;
+        let stencil = self.stencil;
+        let boundary = self.boundary;
+        let randomness_factor = self.randomness_factor;
+        let collapse_strength = self.collapse_strength;
+        let voting = self.voting;
+        let entanglement_coupling = self.entanglement_coupling;
+        let (bistability, hysteresis) = (self.bistability, self.hysteresis);
+        let dilate_time = self.dilate_time;
+        let obstacles = &self.obstacles;
+        let active_tiles = &self.active_tiles;
+        let tiles_x = self.tiles_x();
+        let (seed, steps) = (self.seed, self.steps);
+        let cells_arc = Arc::new(self.cells.clone());
+
+        self.cells.par_iter_mut().enumerate().for_each(|(i, row)| {
+            // One generator per row, so seeded runs don't depend on how rayon splits the work
+            let mut rng = match seed {
+                Some(seed) => StdRng::seed_from_u64(row_seed(seed, steps, i)),
+                None => StdRng::seed_from_u64(rand::thread_rng().gen()),
+            };
+            for j in 0..width {
+                // Pinned cells keep their state, so waves reflect off them
+                if obstacles[i][j] {
+                    continue;
+                }
+                if let Some(active) = active_tiles {
+                    if !active[(i / ACTIVITY_TILE) * tiles_x + j / ACTIVITY_TILE] {
+                        continue;
+                    }
+                }
+                let neighbors =
+                    Grid::get_neighbors(&cells_arc, i, j, width, height, stencil, boundary);
+                row[j] = Grid::calculate_new_state(
+                    &cells_arc,
+                    &cells_arc[i][j],
+                    &neighbors,
+                    randomness_factor,
+                    &mut rng,
+                );
+                if entanglement_coupling > 0.0 {
+                    row[j].couple(&cells_arc, entanglement_coupling);
+                }
+                if voting.strength > 0.0 {
+                    row[j].vote(&neighbors, &voting);
+                }
+                if bistability > 0.0 {
+                    row[j].sharpen(bistability);
+                }
+                if hysteresis > 0.0 {
+                    let previous = dominant_state(&cells_arc[i][j].state_probabilities);
+                    row[j].persist(previous, hysteresis);
+                }
+                if collapse_strength > 0.0 {
+                    row[j].collapse(collapse_strength);
+                }
+                if dilate_time {
+                    // High-entropy cells take only part of this step's change
+                    let before = &cells_arc[i][j].state_probabilities;
+                    let dilation = time_dilation(before);
+                    for (prob, old) in row[j].state_probabilities.iter_mut().zip(before) {
+                        *prob = old + dilation * (*prob - old);
+                    }
+                }
+                row[j].time_dilation = time_dilation(&row[j].state_probabilities);
+            }
+        });
+
+        self.steps += 1;
+        self.apply_sources();
+        if self.active_tiles.is_some() {
+            self.update_activity(&cells_arc);
+        }
+    }
+
+    // Measures the whole grid: every cell collapses to one basic state, drawn with its
+    // probabilities as weights. A cell's entangled partners collapse to the same state
+    // as it, unless they were already measured. Pinned cells are left alone.
+    pub fn measure(&mut self) {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(row_seed(!seed, self.steps, 0)),
+            None => StdRng::seed_from_u64(rand::thread_rng().gen()),
+        };
+
+        let mut outcomes: Vec<Vec<Option<usize>>> = vec![vec![None; self.width]; self.height];
+        for i in 0..self.height {
+            for j in 0..self.width {
+                if outcomes[i][j].is_some() || self.obstacles[i][j] {
+                    continue;
+                }
+                let cell = &self.cells[i][j];
+                let probabilities = cell.state_probabilities;
+                let total: f64 = probabilities.iter().sum();
+                let mut draw = rng.gen::<f64>() * total;
+                let mut outcome = 3;
+                for (state, &prob) in probabilities.iter().enumerate() {
+                    if draw < prob {
+                        outcome = state;
+                        break;
+                    }
+                    draw -= prob;
+                }
+
+                outcomes[i][j] = Some(outcome);
+                for (row, col) in [cell.entangled_partner, cell.second_partner]
+                    .into_iter()
+                    .flatten()
+                {
+                    if outcomes[row][col].is_none() && !self.obstacles[row][col] {
+                        outcomes[row][col] = Some(outcome);
+                    }
+                }
+            }
+        }
+
+        for (row, row_outcomes) in self.cells.iter_mut().zip(outcomes) {
+            for (cell, outcome) in row.iter_mut().zip(row_outcomes) {
+                if let Some(outcome) = outcome {
+                    for (state, prob) in cell.state_probabilities.iter_mut().enumerate() {
+                        *prob = if state == outcome { 1.0 } else { 0.0 };
+                    }
+                    cell.time_dilation = 1.0;
+                }
+            }
+        }
+        self.wake_all();
+    }
+
+    // Applies `steps` updates in a row
+    pub fn evolve(&mut self, steps: u32) {
+        for _ in 0..steps {
+            self.update();
+        }
+    }
+
+    fn tiles_x(&self) -> usize {
+        self.width.div_ceil(ACTIVITY_TILE)
+    }
+
+    fn tiles_y(&self) -> usize {
+        self.height.div_ceil(ACTIVITY_TILE)
+    }
+
+    // Switches to the sparse update, which only evolves tiles near recent activity
+    pub fn enable_sparse_update(&mut self) {
+        self.active_tiles = Some(vec![true; self.tiles_x() * self.tiles_y()]);
+    }
+
+    // Marks every tile active again after the cells, or the parameters that evolve them,
+    // were changed outside of update
+    pub fn wake_all(&mut self) {
+        if let Some(active) = self.active_tiles.as_mut() {
+            active.fill(true);
+        }
+    }
+
+    // Decides which tiles the next update evolves. A tile stays awake if any cell in it
+    // or in one of the eight surrounding tiles changed by more than ACTIVITY_THRESHOLD
+    // during the last step (the stencil reaches at most two cells, so one tile of halo
+    // lets waves enter a sleeping tile), or if one of its cells is entangled with a cell
+    // in a changed tile.
+    fn update_activity(&mut self, previous: &[Vec<CellState>]) {
+        let (tiles_x, tiles_y) = (self.tiles_x(), self.tiles_y());
+        let tile_of = |i: usize, j: usize| (i / ACTIVITY_TILE) * tiles_x + j / ACTIVITY_TILE;
+
+        let mut changed = vec![false; tiles_x * tiles_y];
+        for (i, (row, previous_row)) in self.cells.iter().zip(previous).enumerate() {
+            for (j, (cell, before)) in row.iter().zip(previous_row).enumerate() {
+                let moved = cell
+                    .state_probabilities
+                    .iter()
+                    .zip(before.state_probabilities)
+                    .any(|(now, then)| (now - then).abs() > ACTIVITY_THRESHOLD);
+                if moved {
+                    changed[tile_of(i, j)] = true;
+                }
+            }
+        }
+
+        let mut active = vec![false; tiles_x * tiles_y];
+        for tile_row in 0..tiles_y {
+            for tile_col in 0..tiles_x {
+                if !changed[tile_row * tiles_x + tile_col] {
+                    continue;
+                }
+                // Wake the changed tile and its halo, wrapping like the grid does
+                for d_row in [tiles_y - 1, 0, 1] {
+                    for d_col in [tiles_x - 1, 0, 1] {
+                        let row = (tile_row + d_row) % tiles_y;
+                        let col = (tile_col + d_col) % tiles_x;
+                        active[row * tiles_x + col] = true;
+                    }
+                }
+            }
+        }
+
+        for (i, row) in self.cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                let partners = [cell.entangled_partner, cell.second_partner];
+                if partners
+                    .into_iter()
+                    .flatten()
+                    .any(|(partner_row, partner_col)| changed[tile_of(partner_row, partner_col)])
+                {
+                    active[tile_of(i, j)] = true;
+                }
+            }
+        }
+
+        self.active_tiles = Some(active);
+    }
+
+    fn get_neighbors(
+        grid: &[Vec<CellState>],
+        row: usize,
+        col: usize,
+        width: usize,
+        height: usize,
+        stencil: Stencil,
+        boundary: Boundary,
+    ) -> Vec<CellState> {
+        if boundary == Boundary::Toroidal {
+            return Grid::neighbor_coords(row, col, width, height, stencil)
+                .into_iter()
+                .map(|(neighbor_row, neighbor_col)| grid[neighbor_row][neighbor_col])
+                .collect();
+        }
+
+        let empty = CellState {
+            state_probabilities: [0.0; 4],
+            entangled_partner: None,
+            second_partner: None,
+            time_dilation: 1.0,
+        };
+        stencil
+            .offsets()
+            .iter()
+            .map(|&(i_offset, j_offset)| {
+                let neighbor_row = row as isize + i_offset;
+                let neighbor_col = col as isize + j_offset;
+                let inside = (0..height as isize).contains(&neighbor_row)
+                    && (0..width as isize).contains(&neighbor_col);
+                if inside || boundary == Boundary::Reflecting {
+                    let neighbor_row = neighbor_row.clamp(0, height as isize - 1) as usize;
+                    let neighbor_col = neighbor_col.clamp(0, width as isize - 1) as usize;
+                    grid[neighbor_row][neighbor_col]
+                } else {
+                    empty
+                }
+            })
+            .collect()
+    }
+
+    // Coordinates of the distinct cells in the stencil around (row, col), wrapping around
+    // the edges. On grids narrower than the stencil, several offsets wrap onto the same cell
+    // (or back onto the cell itself); those are skipped so no neighbor is counted twice.
+    fn neighbor_coords(
+        row: usize,
+        col: usize,
+        width: usize,
+        height: usize,
+        stencil: Stencil,
+    ) -> Vec<(usize, usize)> {
+        let mut coords = Vec::with_capacity(stencil.offsets().len());
+
+        for &(i_offset, j_offset) in stencil.offsets() {
+            let neighbor_row = (row as isize + i_offset).rem_euclid(height as isize) as usize;
+            let neighbor_col = (col as isize + j_offset).rem_euclid(width as isize) as usize;
+
+            let coord = (neighbor_row, neighbor_col);
+            if coord != (row, col) && !coords.contains(&coord) {
+                coords.push(coord);
+            }
+        }
+
+        coords
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thin_grids_create_and_step_in_bounds() {
+        for (width, height) in [(1, 1), (1, 10), (10, 1)] {
+            for stencil in [Stencil::VonNeumann, Stencil::Moore, Stencil::Extended] {
+                for boundary in [Boundary::Toroidal, Boundary::Reflecting, Boundary::Fixed] {
+                    let mut grid = Grid::new_seeded(width, height, 7).unwrap();
+                    grid.stencil = stencil;
+                    grid.boundary = boundary;
+                    grid.evolve(3);
+                    assert_eq!(grid.cells.len(), height);
+                    assert!(grid.cells.iter().all(|row| row.len() == width));
+                    assert!(!grid.has_diverged());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn thin_grids_do_not_count_wrapped_neighbors_twice() {
+        let sorted = |mut coords: Vec<(usize, usize)>| {
+            coords.sort();
+            coords
+        };
+        assert!(Grid::neighbor_coords(0, 0, 1, 1, Stencil::Moore).is_empty());
+        assert_eq!(
+            sorted(Grid::neighbor_coords(0, 0, 1, 10, Stencil::Moore)),
+            vec![(1, 0), (9, 0)]
+        );
+        assert_eq!(
+            sorted(Grid::neighbor_coords(0, 0, 10, 1, Stencil::Extended)),
+            vec![(0, 1), (0, 2), (0, 8), (0, 9)]
+        );
+    }
+
+    #[test]
+    fn same_seed_gives_identical_cells() {
+        let bytes = |grid: &Grid| {
+            let mut bytes = Vec::new();
+            crate::io::write_cells(&mut bytes, &grid.cells).unwrap();
+            bytes
+        };
+        let mut a = Grid::new_seeded_with(24, 16, InitDistribution::Clustered, 42).unwrap();
+        let mut b = Grid::new_seeded_with(24, 16, InitDistribution::Clustered, 42).unwrap();
+        assert_eq!(bytes(&a), bytes(&b));
+        a.evolve(5);
+        b.evolve(5);
+        assert_eq!(bytes(&a), bytes(&b));
+
+        let c = Grid::new_seeded_with(24, 16, InitDistribution::Clustered, 43).unwrap();
+        assert_ne!(bytes(&a), bytes(&c));
+    }
+
+    #[test]
+    fn pattern_background_counts_as_undecided() {
+        let grid = Grid::with_pattern(16, 16, Pattern::Glider).unwrap();
+        let counts = grid.calculate_state_distribution();
+        assert_eq!(counts.one, 5);
+        assert_eq!(
+            counts.minus_one + counts.complex_i + counts.complex_minus_i,
+            0
+        );
+        assert_eq!(counts.undecided, 16 * 16 - 5);
+    }
+
+    #[test]
+    fn time_dilation_follows_entropy() {
+        assert!((time_dilation(&[1.0, 0.0, 0.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert!(time_dilation(&[0.997, 0.001, 0.001, 0.001]) > 0.97);
+        assert!((time_dilation(&[0.25; 4]) - 0.1).abs() < 1e-9);
+        assert!(time_dilation(&[0.26, 0.25, 0.25, 0.24]) < 0.11);
+    }
+
+    #[test]
+    fn time_dilation_is_refreshed_where_probabilities_are_set() {
+        let fresh = |grid: &Grid| {
+            grid.cells.iter().flatten().all(|cell| {
+                (cell.time_dilation - time_dilation(&cell.state_probabilities)).abs() < 1e-12
+            })
+        };
+        let mut grid = Grid::new_seeded_with(32, 32, InitDistribution::Clustered, 3).unwrap();
+        assert!(fresh(&grid));
+        grid.inject_wave_packet((16.0, 16.0), 4.0, 0.8, 0.0);
+        assert!(fresh(&grid));
+        grid.sources
+            .push(DrivenSource::parse("5,5,0.3,0.9").unwrap());
+        grid.apply_sources();
+        assert!(fresh(&grid));
+    }
+
+    #[test]
+    fn stencils_give_different_normalized_results() {
+        let evolved = |stencil| {
+            let mut grid = Grid::new_seeded(16, 16, 5).unwrap();
+            grid.stencil = stencil;
+            grid.evolve(4);
+            grid.cells
+        };
+        let von_neumann = evolved(Stencil::VonNeumann);
+        let moore = evolved(Stencil::Moore);
+
+        let probabilities = |cells: &[Vec<CellState>]| -> Vec<[f64; 4]> {
+            cells
+                .iter()
+                .flatten()
+                .map(|cell| cell.state_probabilities)
+                .collect()
+        };
+        assert_ne!(probabilities(&von_neumann), probabilities(&moore));
+        for cell in von_neumann.iter().chain(&moore).flatten() {
+            let total: f64 = cell.state_probabilities.iter().sum();
+            assert!((total - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn uniform_grid_is_unchanged_without_voting() {
+        let mut grid = Grid::with_pattern(12, 12, Pattern::Empty).unwrap();
+        grid.randomness_factor = 0.0;
+        grid.voting.strength = 0.0;
+        let before = grid.cells.clone();
+        grid.update();
+        for (cell, old) in grid.cells.iter().flatten().zip(before.iter().flatten()) {
+            assert_eq!(cell.state_probabilities, old.state_probabilities);
+        }
+    }
+
+    #[test]
+    fn bistability_sharpens_near_uniform_cells() {
+        let leaning = |bistability| {
+            let mut grid = Grid::with_pattern(8, 8, Pattern::Empty).unwrap();
+            for cell in grid.cells.iter_mut().flatten() {
+                cell.state_probabilities = [0.24, 0.24, 0.28, 0.24];
+            }
+            grid.randomness_factor = 0.0;
+            grid.bistability = bistability;
+            (0..10)
+                .map(|_| {
+                    grid.update();
+                    grid.cells[4][4].state_probabilities[2]
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let sharpened = leaning(0.5);
+        // Rises every step until it settles on certainty
+        assert!(sharpened[0] > 0.28);
+        assert!(sharpened
+            .windows(2)
+            .all(|pair| pair[1] > pair[0] || pair[1] == 1.0));
+        assert_eq!(sharpened[9], 1.0);
+        assert!(leaning(0.0).iter().all(|&prob| (prob - 0.28).abs() < 1e-9));
+    }
+
+    #[test]
+    fn reflecting_boundary_keeps_a_packet_from_wrapping() {
+        // Whether column 0 has changed after a packet near the right edge ran into it
+        let wrapped = |boundary| {
+            let mut grid = Grid::with_pattern(32, 32, Pattern::Empty).unwrap();
+            grid.randomness_factor = 0.0;
+            grid.boundary = boundary;
+            grid.inject_wave_packet((27.0, 16.0), 1.0, 0.8, 0.0);
+            let before = grid.cells.clone();
+            grid.evolve(3);
+            assert_ne!(
+                grid.cells[16][31].state_probabilities,
+                before[16][31].state_probabilities
+            );
+            (0..32)
+                .any(|i| grid.cells[i][0].state_probabilities != before[i][0].state_probabilities)
+        };
+        assert!(wrapped(Boundary::Toroidal));
+        assert!(!wrapped(Boundary::Reflecting));
+    }
+
+    #[test]
+    fn dominant_state_ties_go_to_the_lowest_index() {
+        assert_eq!(dominant_state(&[0.25; 4]), 0);
+        assert_eq!(dominant_state(&[0.1, 0.4, 0.4, 0.1]), 1);
+        assert_eq!(dominant_state(&[0.0, 0.2, 0.4, 0.4]), 2);
+        assert_eq!(dominant_state(&[0.1, 0.2, 0.3, 0.4]), 3);
+    }
+
+    #[test]
+    fn load_rejects_partners_outside_the_grid() {
+        let path = std::env::temp_dir().join(format!("partners-{}.qcon", std::process::id()));
+        let mut cells = Grid::new_seeded(4, 3, 8).unwrap().cells;
+        io::save_state(&path, &cells).unwrap();
+        assert!(Grid::load(&path).is_ok());
+
+        cells[1][2].entangled_partner = Some((3, 0));
+        io::save_state(&path, &cells).unwrap();
+        assert!(Grid::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parameter_changes_wake_sleeping_tiles() {
+        let mut grid = Grid::with_pattern(64, 64, Pattern::Empty).unwrap();
+        grid.randomness_factor = 0.0;
+        grid.enable_sparse_update();
+        grid.evolve(2);
+        let asleep = |grid: &Grid| grid.active_tiles.as_ref().unwrap().iter().all(|&a| !a);
+        assert!(asleep(&grid));
+
+        let changes: [fn(&mut Grid); 5] = [
+            |grid| grid.adjust_collapse(0.05),
+            |grid| grid.adjust_bistability(0.05),
+            |grid| grid.adjust_hysteresis(0.05),
+            |grid| grid.scale_randomness(2.0),
+            |grid| grid.apply_config(&Config::default()),
+        ];
+        for change in changes {
+            grid.active_tiles.as_mut().unwrap().fill(false);
+            change(&mut grid);
+            assert!(grid.active_tiles.as_ref().unwrap().iter().all(|&a| a));
+        }
+    }
+
+    #[test]
+    fn empty_grids_are_rejected() {
+        assert!(Grid::new_default(0, 5).is_err());
+        assert!(Grid::new_default(5, 0).is_err());
+    }
+
+    #[test]
+    fn zero_entanglement_probability_gives_no_partners() {
+        let unentangled = |grid: &Grid| {
+            grid.cells
+                .iter()
+                .flatten()
+                .all(|cell| cell.entangled_partner.is_none() && cell.second_partner.is_none())
+        };
+        let grid = Grid::new(32, 32, 0.0).unwrap();
+        assert!(unentangled(&grid));
+        assert!(unentangled(
+            &grid.resized(64, 64, InitDistribution::Clustered).unwrap()
+        ));
+        assert!(!unentangled(&Grid::new_default(32, 32).unwrap()));
+    }
+
+    #[test]
+    fn local_partners_stay_within_distance() {
+        for max_distance in [1, 2, 5] {
+            let entanglement = Entanglement {
+                max_distance: Some(max_distance),
+                second_partner: true,
+                ..Entanglement::default()
+            };
+            let grid =
+                Grid::with_entanglement(20, 12, InitDistribution::Uniform, entanglement, Some(9))
+                    .unwrap();
+            for (i, row) in grid.cells.iter().enumerate() {
+                for (j, cell) in row.iter().enumerate() {
+                    for (pi, pj) in [cell.entangled_partner, cell.second_partner]
+                        .into_iter()
+                        .flatten()
+                    {
+                        assert_ne!((pi, pj), (i, j));
+                        // Distance around the torus, so partners across an edge count as near
+                        let dr = wrapped_offset(pi as f64 - i as f64, 12.0).abs();
+                        let dc = wrapped_offset(pj as f64 - j as f64, 20.0).abs();
+                        assert!(dr.max(dc) <= max_distance as f64);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cells_are_never_their_own_partner() {
+        let grid = Grid::new_with_local_entanglement(8, 8, 0).unwrap();
+        assert!(grid
+            .cells
+            .iter()
+            .flatten()
+            .all(|cell| cell.entangled_partner.is_none()));
+        let grid = Grid::new_default(1, 1).unwrap();
+        assert!(grid.cells[0][0].entangled_partner.is_none());
+        let grid = Grid::new_seeded(2, 1, 4).unwrap();
+        for (j, cell) in grid.cells[0].iter().enumerate() {
+            assert!(cell
+                .entangled_partner
+                .is_none_or(|partner| partner == (0, 1 - j)));
+        }
+    }
+
+    #[test]
+    fn second_partner_is_opt_in() {
+        let grid = Grid::new_default(32, 32).unwrap();
+        assert!(grid
+            .cells
+            .iter()
+            .flatten()
+            .all(|cell| cell.second_partner.is_none()));
+
+        let entanglement = Entanglement {
+            second_partner: true,
+            ..Entanglement::default()
+        };
+        let grid =
+            Grid::with_entanglement(32, 32, InitDistribution::Uniform, entanglement, Some(1))
+                .unwrap();
+        assert!(grid
+            .cells
+            .iter()
+            .flatten()
+            .any(|cell| cell.second_partner.is_some()));
+    }
+
+    #[test]
+    fn write_config_reverses_apply_config() {
+        let mut grid = Grid::new_seeded(12, 8, 1).unwrap();
+        grid.adjust_collapse(0.35);
+        grid.adjust_hysteresis(0.1);
+        grid.scale_randomness(2.0);
+        grid.voting.birth_count = 4;
+        let mut config = Config::default();
+        grid.write_config(&mut config);
+        assert_eq!((config.grid_width, config.grid_height), (12, 8));
+
+        let mut fresh = Grid::new_seeded(12, 8, 2).unwrap();
+        fresh.apply_config(&config);
+        assert_eq!(fresh.collapse_strength, grid.collapse_strength);
+        assert_eq!(fresh.hysteresis, grid.hysteresis);
+        assert_eq!(fresh.randomness_factor, grid.randomness_factor);
+        assert_eq!(fresh.voting, grid.voting);
+        assert_eq!(fresh.entanglement, grid.entanglement);
+    }
+
+    #[test]
+    fn source_parse_rejects_fractional_positions_and_non_finite_numbers() {
+        let source = DrivenSource::parse("3, 7, 0.25, 2").unwrap();
+        assert_eq!((source.col, source.row), (3, 7));
+        assert_eq!((source.frequency, source.amplitude), (0.25, 1.0));
+
+        for value in [
+            "1.5,2,0.1,1",
+            "-1,2,0.1,1",
+            "1,2,NaN,1",
+            "1,2,inf,1",
+            "1,2,0.1,NaN",
+            "1,2,0.1",
+        ] {
+            assert!(DrivenSource::parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn vote_applies_survival_birth_and_ignores_undecided_neighbors() {
+        let rules = VotingRules {
+            strength: 0.5,
+            survival_min: 2,
+            survival_max: 3,
+            birth_count: 3,
+        };
+        let cell = |state_probabilities| CellState {
+            state_probabilities,
+            entangled_partner: None,
+            second_partner: None,
+            time_dilation: 1.0,
+        };
+        let leaning = |state: usize| {
+            let mut probabilities = [0.1; 4];
+            probabilities[state] = 0.7;
+            cell(probabilities)
+        };
+        let undecided = cell([0.25; 4]);
+        let voted = |neighbors: &[CellState]| {
+            let mut voter = cell([0.4, 0.2, 0.2, 0.2]);
+            voter.vote(neighbors, &rules);
+            voter.state_probabilities
+        };
+
+        // 2 or 3 neighbors sharing the dominant state reinforce it, 4 weaken it
+        assert!(voted(&[leaning(0), leaning(0)])[0] > 0.4);
+        assert!(voted(&[leaning(0), leaning(0), leaning(0)])[0] > 0.4);
+        assert!(voted(&[leaning(0); 4])[0] < 0.4);
+
+        // Exactly birth_count neighbors grow another state relative to the rest
+        let born = voted(&[leaning(2); 3]);
+        assert!(born[2] > born[1]);
+        let not_born = voted(&[leaning(2); 2]);
+        assert!((not_born[2] - not_born[1]).abs() < 1e-12);
+
+        // Undecided neighbors count toward no state
+        assert_eq!(
+            voted(&[leaning(0), leaning(0), undecided, undecided]),
+            voted(&[leaning(0), leaning(0)])
+        );
+        assert_eq!(voted(&[undecided; 3]), voted(&[]));
+    }
+}
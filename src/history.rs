@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+
+use crate::grid::CellState;
+
+// Grid state at one point of the run
+pub(crate) struct Snapshot {
+    pub(crate) step: usize,     // Step count shown in the window
+    pub(crate) grid_steps: u64, // The grid's own step count, which drives sources and seeded noise
+    pub(crate) cells: Vec<Vec<CellState>>,
+}
+
+// Ring of recent grid states, captured every few steps, that can be stepped back and
+// forth through from the keyboard. While a past state is on screen the simulation is
+// paused; stepping forward past the newest snapshot returns to the live state.
+pub(crate) struct History {
+    snapshots: VecDeque<Snapshot>, // Oldest first
+    depth: usize,                  // Most snapshots kept; 0 disables the history
+    budget: usize,                 // Most bytes of cells kept, which may hold fewer than depth
+    interval: usize,               // Steps between snapshots
+    position: Option<usize>,       // Snapshot on screen, or None while running live
+    live: Option<Snapshot>,        // State to return to once stepped past the newest snapshot
+}
+
+impl History {
+    pub(crate) fn new(depth: usize, interval: usize, budget: usize) -> History {
+        History {
+            snapshots: VecDeque::new(),
+            depth,
+            budget,
+            interval: interval.max(1),
+            position: None,
+            live: None,
+        }
+    }
+
+    // Called before each update with the state about to be replaced
+    pub(crate) fn record(&mut self, step: usize, grid_steps: u64, cells: &[Vec<CellState>]) {
+        if self.depth == 0 || !step.is_multiple_of(self.interval) {
+            return;
+        }
+        let keep = self.capacity(cells);
+        if keep == 0 {
+            return;
+        }
+        while self.snapshots.len() >= keep {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot {
+            step,
+            grid_steps,
+            cells: cells.to_vec(),
+        });
+    }
+
+    // Most snapshots of a grid this size that fit in the budget, at most depth
+    pub(crate) fn capacity(&self, cells: &[Vec<CellState>]) -> usize {
+        self.depth.min(self.budget / Self::size_of(cells).max(1))
+    }
+
+    fn size_of(cells: &[Vec<CellState>]) -> usize {
+        cells.iter().map(Vec::len).sum::<usize>() * std::mem::size_of::<CellState>()
+    }
+
+    pub(crate) fn is_rewound(&self) -> bool {
+        self.position.is_some()
+    }
+
+    // The snapshot before the one on screen, stashing the live state on the first step
+    // back. None once the oldest snapshot is reached.
+    pub(crate) fn back(
+        &mut self,
+        step: usize,
+        grid_steps: u64,
+        cells: &[Vec<CellState>],
+    ) -> Option<&Snapshot> {
+        let index = match self.position {
+            None => {
+                let index = self.snapshots.len().checked_sub(1)?;
+                self.live = Some(Snapshot {
+                    step,
+                    grid_steps,
+                    cells: cells.to_vec(),
+                });
+                index
+            }
+            Some(0) => return None,
+            Some(position) => position - 1,
+        };
+        self.position = Some(index);
+        self.snapshots.get(index)
+    }
+
+    // The snapshot after the one on screen, or the stashed live state after the newest.
+    // None while already running live.
+    pub(crate) fn forward(&mut self) -> Option<&Snapshot> {
+        let position = self.position?;
+        if position + 1 < self.snapshots.len() {
+            self.position = Some(position + 1);
+            self.snapshots.get(position + 1)
+        } else {
+            self.position = None;
+            self.live.as_ref()
+        }
+    }
+
+    // Forgets every snapshot and resumes live, e.g. after the grid was replaced
+    pub(crate) fn clear(&mut self) {
+        self.snapshots.clear();
+        self.position = None;
+        self.live = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    #[test]
+    fn record_keeps_within_depth_and_budget() {
+        let grid = Grid::new_seeded(8, 8, 1).unwrap();
+        let size = History::size_of(&grid.cells);
+
+        let mut history = History::new(4, 1, usize::MAX);
+        for step in 0..10 {
+            history.record(step, step as u64, &grid.cells);
+        }
+        assert_eq!(history.snapshots.len(), 4);
+        assert_eq!(history.snapshots.front().unwrap().step, 6);
+
+        let mut history = History::new(1_000_000, 1, 3 * size);
+        for step in 0..10 {
+            history.record(step, step as u64, &grid.cells);
+        }
+        assert_eq!(history.snapshots.len(), 3);
+
+        let mut history = History::new(16, 1, size - 1);
+        history.record(0, 0, &grid.cells);
+        assert!(history.snapshots.is_empty());
+        assert!(history.back(0, 0, &grid.cells).is_none());
+    }
+}
@@ -0,0 +1,294 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+#[cfg(feature = "exr")]
+use exr::prelude::*;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{ColorType, Delay, Frame, RgbaImage};
+
+use crate::grid::{time_dilation, CellState, Grid};
+
+// Writes tightly packed RGBA8 pixels as a binary PPM image, dropping the alpha channel
+pub fn save_ppm(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    rgba: &[u8],
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+    for pixel in rgba.chunks_exact(4) {
+        file.write_all(&pixel[..3])?;
+    }
+    file.flush()
+}
+
+// Writes the grid's probabilities as a NumPy .npy array of little-endian f64 with
+// shape (height, width, 4), so it loads directly with `numpy.load`
+pub fn save_npy(
+    path: impl AsRef<Path>,
+    cells: &[Vec<CellState>],
+    width: usize,
+    height: usize,
+) -> io::Result<()> {
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}, 4), }}",
+        height, width
+    );
+    // Magic, version and header length take 10 bytes; the header is padded with spaces
+    // and a trailing newline so the data starts on a 64-byte boundary
+    let unpadded_len = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded_len % 64) % 64));
+    header.push('\n');
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x93NUMPY\x01\x00")?;
+    file.write_all(&(header.len() as u16).to_le_bytes())?;
+    file.write_all(header.as_bytes())?;
+    for row in cells {
+        for cell in row {
+            for prob in cell.state_probabilities {
+                file.write_all(&prob.to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
+
+// Writes tightly packed RGBA8 pixels as a PNG image
+pub fn save_png(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    rgba: &[u8],
+) -> image::ImageResult<()> {
+    image::save_buffer(path, rgba, width as u32, height as u32, ColorType::Rgba8)
+}
+
+// Streams RGBA8 frames into a looping animated GIF, each shown for `delay_ms`
+pub struct GifRecorder {
+    encoder: GifEncoder<BufWriter<File>>,
+    delay_ms: u32,
+}
+
+impl GifRecorder {
+    pub fn create(path: impl AsRef<Path>, delay_ms: u32) -> image::ImageResult<GifRecorder> {
+        let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+        encoder.set_repeat(Repeat::Infinite)?;
+        Ok(GifRecorder { encoder, delay_ms })
+    }
+
+    pub fn add_frame(
+        &mut self,
+        width: usize,
+        height: usize,
+        rgba: Vec<u8>,
+    ) -> image::ImageResult<()> {
+        let image = RgbaImage::from_raw(width as u32, height as u32, rgba)
+            .expect("frame buffer does not match its dimensions");
+        let delay = Delay::from_numer_denom_ms(self.delay_ms, 1);
+        self.encoder
+            .encode_frame(Frame::from_parts(image, 0, 0, delay))
+    }
+}
+
+// Writes one JSON object on its own line with the dominant-state counts and mean time
+// dilation after `step`, for appending to a JSON Lines log
+pub fn write_step_stats(writer: &mut impl Write, step: u64, grid: &Grid) -> io::Result<()> {
+    let counts = grid.calculate_state_distribution();
+    let cells = (grid.width * grid.height) as f64;
+    let mean_time_dilation = grid
+        .cells
+        .iter()
+        .flatten()
+        .map(|cell| cell.time_dilation)
+        .sum::<f64>()
+        / cells;
+    writeln!(
+        writer,
+        "{{\"step\": {}, \"one\": {}, \"minus_one\": {}, \"i\": {}, \"minus_i\": {}, \"undecided\": {}, \"mean_time_dilation\": {:.6}}}",
+        step, counts.one, counts.minus_one, counts.complex_i, counts.complex_minus_i, counts.undecided, mean_time_dilation
+    )
+}
+
+// Writes a grid checkpoint: width and height as little-endian u32s, then the cells
+pub fn save_checkpoint(path: impl AsRef<Path>, cells: &[Vec<CellState>]) -> io::Result<()> {
+    let height = cells.len();
+    let width = cells.first().map_or(0, |row| row.len());
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&(width as u32).to_le_bytes())?;
+    file.write_all(&(height as u32).to_le_bytes())?;
+    write_cells(&mut file, cells)?;
+    file.flush()
+}
+
+// Reads a grid written by `save_checkpoint`
+pub fn load_checkpoint(path: impl AsRef<Path>) -> io::Result<Vec<Vec<CellState>>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let width = read_u32(&mut file)? as usize;
+    let height = read_u32(&mut file)? as usize;
+    read_cells(&mut file, width, height)
+}
+
+const STATE_MAGIC: &[u8; 4] = b"QCON";
+const STATE_VERSION: u32 = 1;
+const CELL_BYTES: u64 = 4 * 8 + 2 * 9; // Size of one cell as written by `write_cells`
+
+// Writes a grid state file: the magic bytes, a format version, the width and height,
+// then the cells as encoded by `write_cells`
+pub fn save_state(path: impl AsRef<Path>, cells: &[Vec<CellState>]) -> io::Result<()> {
+    let height = cells.len();
+    let width = cells.first().map_or(0, |row| row.len());
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(STATE_MAGIC)?;
+    file.write_all(&STATE_VERSION.to_le_bytes())?;
+    file.write_all(&(width as u32).to_le_bytes())?;
+    file.write_all(&(height as u32).to_le_bytes())?;
+    write_cells(&mut file, cells)?;
+    file.flush()
+}
+
+// Reads a grid state file written by `save_state`, checking the header and that the
+// file holds exactly width * height cells
+pub fn load_state(path: impl AsRef<Path>) -> io::Result<Vec<Vec<CellState>>> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut file = BufReader::new(file);
+
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != STATE_MAGIC {
+        return Err(invalid_data("not a grid state file".to_string()));
+    }
+    let version = read_u32(&mut file)?;
+    if version != STATE_VERSION {
+        return Err(invalid_data(format!(
+            "unsupported state file version {} (expected {})",
+            version, STATE_VERSION
+        )));
+    }
+    let width = read_u32(&mut file)? as usize;
+    let height = read_u32(&mut file)? as usize;
+
+    let header_len = 16;
+    let cell_count = (file_len - header_len) / CELL_BYTES;
+    if (file_len - header_len) % CELL_BYTES != 0 || cell_count != (width * height) as u64 {
+        return Err(invalid_data(format!(
+            "header says {}x{} = {} cells but the file holds {} bytes of cell data ({} bytes per cell)",
+            width,
+            height,
+            width * height,
+            file_len - header_len,
+            CELL_BYTES
+        )));
+    }
+    read_cells(&mut file, width, height)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Encodes cells row by row in little-endian binary: each cell's four probabilities as
+// f64s, then each partner as a presence byte followed by its (row, col) as u32s
+pub fn write_cells(writer: &mut impl Write, cells: &[Vec<CellState>]) -> io::Result<()> {
+    for cell in cells.iter().flatten() {
+        for prob in cell.state_probabilities {
+            writer.write_all(&prob.to_le_bytes())?;
+        }
+        for partner in [cell.entangled_partner, cell.second_partner] {
+            match partner {
+                Some((row, col)) => {
+                    writer.write_all(&[1])?;
+                    writer.write_all(&(row as u32).to_le_bytes())?;
+                    writer.write_all(&(col as u32).to_le_bytes())?;
+                }
+                None => writer.write_all(&[0; 9])?,
+            }
+        }
+    }
+    Ok(())
+}
+
+// Decodes `width * height` cells written by `write_cells`
+pub fn read_cells(
+    reader: &mut impl Read,
+    width: usize,
+    height: usize,
+) -> io::Result<Vec<Vec<CellState>>> {
+    (0..height)
+        .map(|_| {
+            (0..width)
+                .map(|_| {
+                    let mut state_probabilities = [0.0; 4];
+                    for prob in &mut state_probabilities {
+                        let mut bytes = [0; 8];
+                        reader.read_exact(&mut bytes)?;
+                        *prob = f64::from_le_bytes(bytes);
+                    }
+                    Ok(CellState {
+                        state_probabilities,
+                        entangled_partner: read_partner(reader)?,
+                        second_partner: read_partner(reader)?,
+                        time_dilation: time_dilation(&state_probabilities),
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn read_partner(reader: &mut impl Read) -> io::Result<Option<(usize, usize)>> {
+    let mut present = [0; 1];
+    reader.read_exact(&mut present)?;
+    let row = read_u32(reader)? as usize;
+    let col = read_u32(reader)? as usize;
+    Ok((present[0] != 0).then_some((row, col)))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+// Channel names for the basic states, in the same order as `state_probabilities`
+#[cfg(feature = "exr")]
+const CHANNEL_NAMES: [&str; 4] = ["one", "minus_one", "i", "minus_i"];
+
+// Writes the grid as a multi-channel OpenEXR image with one 32-bit float channel per
+// basic state, so the probabilities keep their precision instead of being clamped to 8 bits
+#[cfg(feature = "exr")]
+pub fn save_exr(
+    path: impl AsRef<Path>,
+    cells: &[Vec<CellState>],
+    width: usize,
+    height: usize,
+) -> exr::error::Result<()> {
+    let channels: Vec<AnyChannel<FlatSamples>> = CHANNEL_NAMES
+        .iter()
+        .enumerate()
+        .map(|(state, &name)| {
+            let samples = cells
+                .iter()
+                .flat_map(|row| {
+                    row.iter()
+                        .map(move |cell| cell.state_probabilities[state] as f32)
+                })
+                .collect();
+            AnyChannel::new(name, FlatSamples::F32(samples))
+        })
+        .collect();
+
+    let layer = Layer::new(
+        (width, height),
+        LayerAttributes::named("quantum-life"),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels.into()),
+    );
+
+    Image::from_layer(layer).write().to_file(path)
+}
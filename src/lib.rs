@@ -0,0 +1,30 @@
+extern crate image;
+extern crate log;
+extern crate piston_window;
+extern crate rand;
+extern crate rayon;
+extern crate serde;
+extern crate toml;
+
+mod app;
+pub mod bench;
+mod checkpoint;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod config;
+mod grid;
+mod history;
+pub mod io;
+mod pattern;
+mod render;
+mod stats;
+pub mod sweep;
+
+pub use app::{run_headless, App};
+pub use config::Config;
+pub use grid::{
+    dominant_state, is_undecided, time_dilation, Boundary, CellState, DrivenSource, Entanglement,
+    Grid, InitDistribution, MemoryUsage, StateDistribution, Stencil, VotingRules,
+};
+pub use pattern::Pattern;
+pub use render::{frame_rgba, render_to_rgba, Colormap, RenderMode, ToneCurve, ToneMapping, View};
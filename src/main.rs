@@ -1,323 +1,587 @@
-extern crate piston_window;
-extern crate rand;
-extern crate rayon;
-
-use rand::Rng;
-use std::sync::Arc;
-
-use piston_window::{clear, rectangle, PistonWindow, WindowSettings};
-use rayon::prelude::*;
-use std::time::{Duration, Instant};
-#[derive(Clone, Debug, Copy)]
-enum BasicState {
-    One,
-    MinusOne,
-    ComplexI,
-    ComplexMinusI,
-}
-
-#[derive(Clone, Debug, Copy)]
-struct CellState {
-    state_probabilities: [f64; 4], // Probabilities for each basic state
-    entangled_partner: Option<(usize, usize)>, // Optional entangled partner coordinates
-}
-
-impl Grid {
-    fn calculate_state_distribution(&self) -> StateDistribution {
-        let mut distribution = StateDistribution {
-            one: 0,
-            minus_one: 0,
-            complex: 0,
+extern crate complex_conway;
+extern crate env_logger;
+extern crate log;
+
+use complex_conway::bench::{run_bench_sweep, write_bench_json};
+use complex_conway::io;
+use complex_conway::sweep::{run_sweep, Sweep};
+use complex_conway::{
+    frame_rgba, App, Boundary, Colormap, Config, DrivenSource, Grid, InitDistribution, Pattern,
+    RenderMode, Stencil, ToneMapping, View,
+};
+use log::{error, info, LevelFilter};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+// Every command-line flag: its name, the placeholder for its value if it takes one,
+// and what it does. Anything else on the command line is rejected.
+const FLAGS: &[(&str, Option<&str>, &str)] = &[
+    ("--help", None, "Print this message and exit"),
+    ("--config", Some("<path>"), "Read settings from a TOML file"),
+    ("--grid", Some("<WxH>"), "Grid size in cells, e.g. 256x128"),
+    (
+        "--max-fps",
+        Some("<fps>"),
+        "Frame rate cap for the window; 0 leaves it uncapped",
+    ),
+    ("--vsync", None, "Sync buffer swaps to the display refresh"),
+    ("--msaa", Some("<samples>"), "Multisampling: 0, 2, 4 or 8"),
+    (
+        "--init-dist",
+        Some("<dist>"),
+        "Starting probabilities: uniform, clustered, sparse or sparse:<density>",
+    ),
+    (
+        "--seed",
+        Some("<n>"),
+        "Seed every random draw, so runs are reproducible",
+    ),
+    (
+        "--pattern",
+        Some("<name>"),
+        "Start from random, glider, blinker, center-pulse or empty",
+    ),
+    (
+        "--init-image",
+        Some("<path>"),
+        "Start from an image's brightness",
+    ),
+    (
+        "--no-entanglement",
+        None,
+        "Start, resize and re-randomize without entangled partners",
+    ),
+    (
+        "--stencil",
+        Some("<name>"),
+        "Neighborhood: von-neumann, moore or extended",
+    ),
+    (
+        "--boundary",
+        Some("<name>"),
+        "Grid edges: toroidal, reflecting or fixed",
+    ),
+    (
+        "--source",
+        Some("<x,y,freq,amp>"),
+        "Drive a cell as a wave source; may be repeated",
+    ),
+    (
+        "--sparse-update",
+        None,
+        "Only evolve tiles that are still changing",
+    ),
+    (
+        "--demo",
+        Some("packet"),
+        "Launch a wave packet across the middle of the grid",
+    ),
+    ("--headless", None, "Evolve without opening a window"),
+    (
+        "--steps",
+        Some("<n>"),
+        "Steps to evolve without a window (default 0)",
+    ),
+    (
+        "--dump-npy",
+        Some("<path>"),
+        "Write the evolved grid as a NumPy array",
+    ),
+    (
+        "--export-tile",
+        Some("<path>"),
+        "Write the evolved grid as a tileable PNG",
+    ),
+    (
+        "--record",
+        Some("<path>"),
+        "Record frames to a GIF, or raw RGBA to stdout with -",
+    ),
+    ("--frames", Some("<n>"), "Frames to record (default 100)"),
+    (
+        "--width",
+        Some("<pixels>"),
+        "Approximate width of recorded frames",
+    ),
+    (
+        "--sweep",
+        Some("<name:a..b:steps>"),
+        "Ramp a config parameter over the steps, saving a PNG every 10, and exit",
+    ),
+    (
+        "--stats-out",
+        Some("<path>"),
+        "Append per-step state counts as JSON Lines",
+    ),
+    (
+        "--step-marker",
+        None,
+        "Draw a corner square that changes color every step",
+    ),
+    (
+        "--frame-stats",
+        None,
+        "Print frame time percentiles on exit",
+    ),
+    (
+        "--state-histogram",
+        None,
+        "Log the dominant state counts once a second",
+    ),
+    (
+        "--bench-sweep",
+        None,
+        "Time updates over a range of grid sizes and exit",
+    ),
+    (
+        "--bench-json",
+        Some("<path>"),
+        "Write the --bench-sweep results as JSON",
+    ),
+    ("--quiet", None, "Only log errors"),
+    ("--verbose", None, "Log debug messages"),
+    (
+        "--log-level",
+        Some("<level>"),
+        "Log level: error, warn, info, debug or trace",
+    ),
+];
+
+// The --help text, listing every flag
+fn usage() -> String {
+    let mut text = String::from("Usage: complex-conway [options]\n\nOptions:\n");
+    for (flag, value, description) in FLAGS {
+        let name = match value {
+            Some(value) => format!("{} {}", flag, value),
+            None => flag.to_string(),
         };
+        text.push_str(&format!("  {:<28} {}\n", name, description));
+    }
+    text
+}
 
-        for row in &self.cells {
-            for cell in row {
-                let max_index = cell
-                    .state_probabilities
-                    .iter()
-                    .enumerate()
-                    .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                    .map(|(index, _)| index);
-
-                match max_index {
-                    Some(0) => distribution.one += 1,
-                    Some(1) => distribution.minus_one += 1,
-                    Some(2) | Some(3) => distribution.complex += 1,
-                    _ => {}
-                }
+// Rejects unknown flags, stray arguments and flags missing their value
+fn check_args(args: &[String]) -> Result<(), String> {
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        let (flag, value, _) = FLAGS
+            .iter()
+            .find(|(flag, _, _)| flag == arg)
+            .ok_or_else(|| format!("unknown argument '{}'", arg))?;
+        if let Some(placeholder) = value {
+            match rest.next() {
+                Some(value) if !value.starts_with("--") => {}
+                _ => return Err(format!("{} needs a value {}", flag, placeholder)),
             }
         }
-
-        distribution
     }
+    Ok(())
+}
 
-    fn calculate_new_state(grid: &Vec<Vec<CellState>>, current_state: &CellState, neighbors: &Vec<CellState>) -> CellState {
-        let mut new_state = current_state.clone();
-        let mut rng = rand::thread_rng();
-
-        // Reduce the randomness factor
-        let randomness_factor = 0.01; // Smaller randomness factor
-
-        // Entanglement Logic - enhanced for more structured behavior
-        if let Some((partner_x, partner_y)) = current_state.entangled_partner {
-            let partner_state = &grid[partner_x][partner_y];
-
-            // Example: Synchronize states if certain conditions are met
-            for i in 0..4 {
-                if rng.gen::<f64>() < randomness_factor {
-                    new_state.state_probabilities[i] = (new_state.state_probabilities[i] + partner_state.state_probabilities[i]) / 2.0;
-                }
-            }
-        }
+// Parses a grid size given as `WIDTHxHEIGHT`, e.g. `256x128`
+fn parse_grid_size(value: &str) -> Result<(usize, usize), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got '{}'", value))?;
+    let width = width
+        .parse::<usize>()
+        .map_err(|_| format!("invalid width '{}'", width))?;
+    let height = height
+        .parse::<usize>()
+        .map_err(|_| format!("invalid height '{}'", height))?;
+    Ok((width, height))
+}
 
-        // Calculate the weighted influence of neighbors
-        let mut neighbor_influence = [0.0; 4];
-        for neighbor in neighbors {
-            for (i, &prob) in neighbor.state_probabilities.iter().enumerate() {
-                neighbor_influence[i] += prob;
+// Sets up logging to stderr. Defaults to info, or to RUST_LOG when it is set;
+// `--quiet`, `--verbose` and `--log-level <level>` override both. An unknown level is
+// an error, reported once the default logger is up.
+fn init_logging(args: &[String]) -> Result<(), String> {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(LevelFilter::Info).parse_default_env();
+
+    let mut result = Ok(());
+    if args.iter().any(|arg| arg == "--quiet") {
+        builder.filter_level(LevelFilter::Error);
+    } else if args.iter().any(|arg| arg == "--verbose") {
+        builder.filter_level(LevelFilter::Debug);
+    } else if let Some(value) = arg_value(args, "--log-level") {
+        match value.parse::<LevelFilter>() {
+            Ok(level) => {
+                builder.filter_level(level);
             }
-        }
-
-        // Normalize the influence
-        let total_influence: f64 = neighbor_influence.iter().sum();
-        if total_influence > 0.0 {
-            for influence in &mut neighbor_influence {
-                *influence /= total_influence;
+            Err(_) => {
+                result = Err(format!(
+                    "Invalid --log-level: '{}', expected error, warn, info, debug or trace",
+                    value
+                ))
             }
         }
-
-        // Update state probabilities based on neighbor influence and some randomness
-        for (i, prob) in new_state.state_probabilities.iter_mut().enumerate() {
-            *prob = (*prob + neighbor_influence[i]) / 2.0;
-            *prob += rng.gen::<f64>() * randomness_factor; // Reduced randomness
-        }
-
-        // Ensure probabilities sum to 1
-        let total_prob: f64 = new_state.state_probabilities.iter().sum();
-        for prob in &mut new_state.state_probabilities {
-            *prob /= total_prob;
-        }
-
-        new_state
     }
-}
 
-struct Grid {
-    cells: Vec<Vec<CellState>>,
-    width: usize,
-    height: usize,
+    builder.init();
+    result
 }
 
-// A struct to hold counts of different types of cell states for the entire grid
-struct StateDistribution {
-    one: usize,
-    minus_one: usize,
-    complex: usize,
+// Value following `flag` on the command line, e.g. `--bench-json out.json`
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.as_str())
 }
 
-impl Grid {
-    fn new(width: usize, height: usize) -> Grid {
-        let mut rng = rand::thread_rng();
-        let cells = (0..height)
-            .map(|_| {
-                (0..width)
-                    .map(|_| {
-                        // Random probabilities for each state
-                        let mut probs = [0.0; 4];
-                        for p in &mut probs {
-                            *p = rng.gen::<f64>();
-                        }
-                        let sum: f64 = probs.iter().sum();
-                        for p in &mut probs {
-                            *p /= sum; // Normalize probabilities to sum to 1
-                        }
-
-                        // Randomly assign entangled partners (for simplicity, could be improved)
-                        let entangled_partner = if rng.gen::<f64>() < 0.88 { // 30% chance of entanglement
-                            Some((rng.gen_range(0..width), rng.gen_range(0..height)))
-                        } else {
-                            None
-                        };
+// Logs a readable error and exits, for bad command-line arguments or settings
+fn fail(message: String) -> ! {
+    error!("{}", message);
+    std::process::exit(2)
+}
 
-                        CellState {
-                            state_probabilities: probs,
-                            entangled_partner,
-                        }
-                    })
-                    .collect()
-            })
-            .collect();
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(err) = init_logging(&args) {
+        fail(err);
+    }
+    if let Err(err) = check_args(&args) {
+        error!("{}", err);
+        eprint!("{}", usage());
+        std::process::exit(2);
+    }
+    if args.iter().any(|arg| arg == "--help") {
+        print!("{}", usage());
+        return;
+    }
 
-        Grid {
-            cells,
-            width,
-            height,
+    if args.iter().any(|arg| arg == "--bench-sweep") {
+        let results = run_bench_sweep();
+        if let Some(path) = arg_value(&args, "--bench-json") {
+            match write_bench_json(path, &results) {
+                Ok(()) => info!("Wrote benchmark report to {}", path),
+                Err(err) => error!("Failed to write {}: {}", path, err),
+            }
         }
+        return;
     }
 
-    fn update(&mut self) {
-        let width = self.width;
-        let height = self.height;
-        let cells_arc = Arc::new(self.cells.clone());
-
-        self.cells.par_iter_mut().enumerate().for_each(|(i, row)| {
-            for j in 0..width {
-                let neighbors = Grid::get_neighbors(&cells_arc, i, j, width, height);
-                row[j] = Grid::calculate_new_state(&cells_arc, &cells_arc[i][j], &neighbors);
+    let mut config = match arg_value(&args, "--config") {
+        Some(path) => Config::from_toml_file(path)
+            .unwrap_or_else(|e| fail(format!("Invalid --config: {}", e))),
+        None => Config::default(),
+    };
+    if let Some(value) = arg_value(&args, "--grid") {
+        let (width, height) =
+            parse_grid_size(value).unwrap_or_else(|e| fail(format!("Invalid --grid: {}", e)));
+        config.grid_width = width;
+        config.grid_height = height;
+    }
+    if let Some(value) = arg_value(&args, "--max-fps") {
+        config.max_fps = value
+            .parse::<u64>()
+            .unwrap_or_else(|_| fail(format!("Invalid --max-fps: '{}'", value)));
+    }
+    if args.iter().any(|arg| arg == "--vsync") {
+        config.vsync = true;
+    }
+    if let Some(value) = arg_value(&args, "--msaa") {
+        config.msaa_samples = value
+            .parse::<u8>()
+            .unwrap_or_else(|_| fail(format!("Invalid --msaa: '{}'", value)));
+    }
+    if args.iter().any(|arg| arg == "--no-entanglement") {
+        config.partner_probability = 0.0;
+    }
+    config
+        .validate()
+        .unwrap_or_else(|e| fail(format!("Invalid settings: {}", e)));
+    let (grid_width, grid_height) = (config.grid_width, config.grid_height);
+
+    let distribution = match arg_value(&args, "--init-dist") {
+        Some(value) => InitDistribution::parse(value)
+            .unwrap_or_else(|e| fail(format!("Invalid --init-dist: {}", e))),
+        None => InitDistribution::Uniform,
+    };
+    let seed = arg_value(&args, "--seed").map(|value| {
+        value
+            .parse::<u64>()
+            .unwrap_or_else(|_| fail(format!("Invalid --seed: '{}'", value)))
+    });
+    let pattern = arg_value(&args, "--pattern")
+        .map(|value| {
+            Pattern::parse(value).unwrap_or_else(|e| fail(format!("Invalid --pattern: {}", e)))
+        })
+        .filter(|&pattern| pattern != Pattern::Random);
+    let mut grid = match arg_value(&args, "--init-image") {
+        Some(path) => Grid::from_image(path, grid_width, grid_height, config.entanglement(), seed),
+        None => match pattern {
+            // Patterns draw nothing at random, but the seed still fixes their updates
+            Some(pattern) => {
+                Grid::with_pattern(grid_width, grid_height, pattern).map(|mut grid| {
+                    grid.seed = seed;
+                    grid
+                })
             }
-        });
+            None => Grid::with_entanglement(
+                grid_width,
+                grid_height,
+                distribution,
+                config.entanglement(),
+                seed,
+            ),
+        },
     }
-
-    fn get_neighbors(
-        grid: &Vec<Vec<CellState>>,
-        row: usize,
-        col: usize,
-        width: usize,
-        height: usize,
-    ) -> Vec<CellState> {
-        let mut neighbors = Vec::new();
-
-        for i_offset in -1..=1 {
-            for j_offset in -1..=1 {
-                if i_offset == 0 && j_offset == 0 {
-                    continue; // Skip the cell itself
-                }
-
-                let neighbor_row = (row as isize + i_offset).rem_euclid(height as isize) as usize;
-                let neighbor_col = (col as isize + j_offset).rem_euclid(width as isize) as usize;
-
-                neighbors.push(grid[neighbor_row][neighbor_col]);
+    .unwrap_or_else(|e| fail(format!("Failed to create grid: {}", e)));
+    grid.apply_config(&config);
+    if let Some(value) = arg_value(&args, "--stencil") {
+        grid.stencil =
+            Stencil::parse(value).unwrap_or_else(|e| fail(format!("Invalid --stencil: {}", e)));
+    }
+    if let Some(value) = arg_value(&args, "--boundary") {
+        grid.boundary =
+            Boundary::parse(value).unwrap_or_else(|e| fail(format!("Invalid --boundary: {}", e)));
+    }
+    for (flag, value) in args.iter().zip(args.iter().skip(1)) {
+        if flag == "--source" {
+            let source = DrivenSource::parse(value)
+                .unwrap_or_else(|e| fail(format!("Invalid --source: {}", e)));
+            if source.row >= grid_height || source.col >= grid_width {
+                fail(format!(
+                    "Invalid --source: '{}' lies outside the grid",
+                    value
+                ));
             }
+            grid.sources.push(source);
         }
+    }
+    if args.iter().any(|arg| arg == "--sparse-update") {
+        grid.enable_sparse_update();
+    }
+    if arg_value(&args, "--demo") == Some("packet") {
+        // A packet in the middle of the grid moving to the right
+        let center = (grid_width as f64 / 2.0, grid_height as f64 / 2.0);
+        grid.inject_wave_packet(center, 6.0, 0.8, 0.0);
+    }
 
-        neighbors
+    let usage = grid.memory_usage();
+    info!(
+        "Grid memory: {:.1} MB total ({} bytes per cell; cells {} B, update snapshot {} B, obstacles {} B, sources {} B), not counting the window's history and baselines",
+        usage.total() as f64 / (1024.0 * 1024.0),
+        usage.per_cell,
+        usage.cells,
+        usage.snapshot,
+        usage.obstacles,
+        usage.sources
+    );
+
+    // Ramp one parameter while saving frames, then exit
+    if let Some(value) = arg_value(&args, "--sweep") {
+        let sweep = Sweep::parse(value).unwrap_or_else(|e| fail(format!("Invalid --sweep: {}", e)));
+        if let Err(err) = run_sweep(&mut grid, &config, &sweep, &sweep.folder()) {
+            error!("Sweep failed: {}", err);
+            std::process::exit(1);
+        }
+        return;
     }
 
-    fn count_neighbors(&self, row: usize, col: usize) -> NeighborCount {
-        let mut count = NeighborCount {
-            one_or_i: 0,
-            minus_i: 0,
-            i: 0,
+    // Evolve and write the result without ever opening a window
+    let dump_npy = arg_value(&args, "--dump-npy");
+    let export_tile = arg_value(&args, "--export-tile");
+    if export_tile.is_some() && grid.boundary != Boundary::Toroidal {
+        fail(format!(
+            "--export-tile needs the toroidal boundary to tile seamlessly, got {:?}",
+            grid.boundary
+        ));
+    }
+    let record = arg_value(&args, "--record");
+    let stats_out = arg_value(&args, "--stats-out");
+    let headless = args.iter().any(|arg| arg == "--headless");
+    if headless
+        || dump_npy.is_some()
+        || export_tile.is_some()
+        || record.is_some()
+        || stats_out.is_some()
+    {
+        let steps = match arg_value(&args, "--steps") {
+            Some(value) => value
+                .parse::<u32>()
+                .unwrap_or_else(|_| fail(format!("Invalid --steps: '{}'", value))),
+            None => 0,
         };
-
-        for i_offset in -1..=1 {
-            for j_offset in -1..=1 {
-                if i_offset == 0 && j_offset == 0 {
-                    continue;
+        let start = Instant::now();
+        match stats_out {
+            Some(path) => {
+                // One line per step, starting with the initial grid
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .unwrap_or_else(|e| fail(format!("Failed to open {}: {}", path, e)));
+                let mut writer = BufWriter::new(file);
+                let result = (0..=steps)
+                    .try_for_each(|step| {
+                        if step > 0 {
+                            grid.update();
+                        }
+                        io::write_step_stats(&mut writer, step as u64, &grid)
+                    })
+                    .and_then(|()| writer.flush());
+                if let Err(err) = result {
+                    error!("Failed to write {}: {}", path, err);
+                    std::process::exit(1);
                 }
-
-                let neighbor_row =
-                    (row as isize + i_offset).rem_euclid(self.height as isize) as usize;
-                let neighbor_col =
-                    (col as isize + j_offset).rem_euclid(self.width as isize) as usize;
-
-                let cell = self.cells[neighbor_row][neighbor_col];
-                let max_index = cell
-                    .state_probabilities
-                    .iter()
-                    .enumerate()
-                    .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                    .map(|(index, _)| index);
-
-                match max_index {
-                    Some(0) => count.one_or_i += 1,
-                    Some(2) => count.i += 1,       // ComplexI
-                    Some(3) => count.minus_i += 1, // ComplexMinusI
-                    _ => {}
+            }
+            None => grid.evolve(steps),
+        }
+        info!(
+            "Evolved {}x{} grid for {} steps in {:.1} ms",
+            grid_width,
+            grid_height,
+            steps,
+            start.elapsed().as_secs_f64() * 1000.0
+        );
+
+        if let Some(path) = dump_npy {
+            match io::save_npy(path, &grid.cells, grid_width, grid_height) {
+                Ok(()) => info!(
+                    "Wrote {}x{} grid after {} steps to {}",
+                    grid_width, grid_height, steps, path
+                ),
+                Err(err) => {
+                    error!("Failed to write {}: {}", path, err);
+                    std::process::exit(1);
                 }
             }
         }
 
-        count
-    }
-
-    fn sum_neighbors_complex(&self, row: usize, col: usize) -> (f64, f64) {
-        let mut sum_real = 0.0;
-        let mut sum_imaginary = 0.0;
-
-        for i_offset in -1..=1 {
-            for j_offset in -1..=1 {
-                if i_offset == 0 && j_offset == 0 {
-                    continue;
+        if let Some(path) = export_tile {
+            // The boundary is toroidal (checked above), so the rendered frame tiles seamlessly
+            let view = View::new(config.cell_size);
+            let (width, height, pixels) = frame_rgba(
+                &grid,
+                &grid.cells,
+                &view,
+                RenderMode::DominantState,
+                &ToneMapping::new(),
+                Colormap::Classic,
+            );
+            match io::save_png(path, width, height, &pixels) {
+                Ok(()) => info!("Wrote {}x{} tileable image to {}", width, height, path),
+                Err(err) => {
+                    error!("Failed to write {}: {}", path, err);
+                    std::process::exit(1);
                 }
+            }
+        }
 
-                let neighbor_row =
-                    (row as isize + i_offset).rem_euclid(self.height as isize) as usize;
-                let neighbor_col =
-                    (col as isize + j_offset).rem_euclid(self.width as isize) as usize;
+        if let Some(path) = record {
+            let frames = match arg_value(&args, "--frames") {
+                Some(value) => value
+                    .parse::<u32>()
+                    .unwrap_or_else(|_| fail(format!("Invalid --frames: '{}'", value))),
+                None => 100,
+            };
+            // Pick the cell size that brings the frame closest to --width pixels
+            let cell_size = match arg_value(&args, "--width") {
+                Some(value) => {
+                    let width = value
+                        .parse::<usize>()
+                        .unwrap_or_else(|_| fail(format!("Invalid --width: '{}'", value)));
+                    (width / grid_width).max(1)
+                }
+                None => config.cell_size,
+            };
+            let view = View::new(cell_size);
+            let initial = grid.cells.clone();
+            let tone = ToneMapping::new();
+            // "-" streams raw RGBA frames to stdout, e.g. for piping into ffmpeg
+            let mut stdout = std::io::stdout().lock();
+            let mut gif = if path == "-" {
+                None
+            } else {
+                match io::GifRecorder::create(path, config.update_interval_ms as u32) {
+                    Ok(recorder) => Some(recorder),
+                    Err(err) => {
+                        error!("Failed to create {}: {}", path, err);
+                        std::process::exit(1);
+                    }
+                }
+            };
 
-                let cell = self.cells[neighbor_row][neighbor_col];
-                sum_real += cell.state_probabilities[0] - cell.state_probabilities[1]; // One - MinusOne
-                sum_imaginary += cell.state_probabilities[2] - cell.state_probabilities[3];
-                // ComplexI - ComplexMinusI
+            let start = Instant::now();
+            let mut size = (0, 0);
+            for frame in 0..frames {
+                if frame > 0 {
+                    grid.evolve(1);
+                }
+                let (width, height, pixels) = frame_rgba(
+                    &grid,
+                    &initial,
+                    &view,
+                    RenderMode::DominantState,
+                    &tone,
+                    Colormap::Classic,
+                );
+                size = (width, height);
+                let result = match gif.as_mut() {
+                    Some(recorder) => recorder
+                        .add_frame(width, height, pixels)
+                        .map_err(|e| e.to_string()),
+                    None => stdout.write_all(&pixels).map_err(|e| e.to_string()),
+                };
+                if let Err(err) = result {
+                    error!("Failed to write frame {} to {}: {}", frame, path, err);
+                    std::process::exit(1);
+                }
             }
+            info!(
+                "Recorded {} {}x{} frames to {} in {:.1} s",
+                frames,
+                size.0,
+                size.1,
+                path,
+                start.elapsed().as_secs_f64()
+            );
         }
-
-        (sum_real, sum_imaginary)
+        return;
     }
-}
-// A struct to hold counts of different types of neighbors
-struct NeighborCount {
-    one_or_i: usize,
-    minus_i: usize,
-    i: usize,
-}
 
-fn main() {
-    let grid_width = 100;
-    let grid_height = 100;
-    let cell_size = 7; // Size of each cell in pixels
+    let mut app =
+        App::with_grid(grid, config).unwrap_or_else(|e| fail(format!("Invalid settings: {}", e)));
+    app.distribution = distribution;
+    app.step_marker = args.iter().any(|arg| arg == "--step-marker");
+    app.frame_stats = args.iter().any(|arg| arg == "--frame-stats");
+    app.state_histogram = args.iter().any(|arg| arg == "--state-histogram");
+    app.run();
+}
 
-    let mut grid = Grid::new(grid_width, grid_height);
-    let mut window: PistonWindow = WindowSettings::new(
-        "Quantum Life",
-        [
-            (grid_width * cell_size) as u32,
-            (grid_height * cell_size) as u32,
-        ],
-    )
-    .exit_on_esc(true)
-    .build()
-    .unwrap_or_else(|e| panic!("Failed to build PistonWindow: {}", e));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut last_update = Instant::now();
-    let update_interval = Duration::from_millis(300); // ~ 3 times a second
+    fn args(line: &str) -> Vec<String> {
+        std::iter::once("complex-conway")
+            .chain(line.split_whitespace())
+            .map(String::from)
+            .collect()
+    }
 
-    while let Some(e) = window.next() {
-        if last_update.elapsed() >= update_interval {
-            grid.update(); // Update the grid
-            last_update = Instant::now();
-        }
+    #[test]
+    fn known_flags_are_accepted() {
+        assert!(check_args(&args("")).is_ok());
+        assert!(check_args(&args("--grid 64x32 --seed 3 --headless --steps 10")).is_ok());
+        assert!(check_args(&args("--source 1,2,0.1,1 --source 3,4,0.1,1 --record -")).is_ok());
+    }
 
-        window.draw_2d(&e, |c, g, _| {
-            clear([1.0; 4], g); // Clear the screen
-            for i in 0..grid_height {
-                for j in 0..grid_width {
-                    let color = {
-                        let state = &grid.cells[i][j];
-                        let max_prob_state = state
-                            .state_probabilities
-                            .iter()
-                            .enumerate()
-                            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                            .map(|(index, _)| index);
+    #[test]
+    fn unknown_flags_and_missing_values_are_rejected() {
+        assert!(check_args(&args("--gird 64x32")).is_err());
+        assert!(check_args(&args("--grid")).is_err());
+        assert!(check_args(&args("--seed --headless")).is_err());
+        assert!(check_args(&args("--headless 10")).is_err());
+    }
 
-                        match max_prob_state {
-                            Some(0) => [0.5, 0.5, 0.8, 1.0], // Color for state 'One'
-                            Some(1) => [0.8, 0.5, 0.5, 1.0], // Color for state 'MinusOne'
-                            Some(2) => [0.5, 0.8, 0.5, 1.0], // Color for state 'ComplexI'
-                            Some(3) => [0.8, 0.8, 0.5, 1.0], // Color for state 'ComplexMinusI'
-                            _ => [0.5, 0.5, 0.5, 1.0],       // Default or error color
-                        }
-                    };
-                    let square = rectangle::square(
-                        (j * cell_size) as f64,
-                        (i * cell_size) as f64,
-                        cell_size as f64,
-                    );
-                    rectangle(color, square, c.transform, g); // Draw the rectangle
-                }
-            }
-        });
+    #[test]
+    fn usage_lists_every_flag() {
+        let text = usage();
+        assert!(FLAGS.iter().all(|(flag, _, _)| text.contains(flag)));
     }
 }
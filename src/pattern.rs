@@ -0,0 +1,109 @@
+// Starting layouts for a grid: known Life structures placed as certain 'One' cells on
+// an otherwise undecided, unentangled background
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum Pattern {
+    Random,      // The usual random grid
+    Glider,      // A glider near the center, heading down and to the right
+    Blinker,     // A period-2 blinker at the center
+    CenterPulse, // A filled disk of radius 2 at the center
+    Empty,       // Background only
+}
+
+impl Pattern {
+    pub fn parse(value: &str) -> Result<Pattern, String> {
+        match value {
+            "random" => Ok(Pattern::Random),
+            "glider" => Ok(Pattern::Glider),
+            "blinker" => Ok(Pattern::Blinker),
+            "center-pulse" => Ok(Pattern::CenterPulse),
+            "empty" => Ok(Pattern::Empty),
+            _ => Err(format!(
+                "unknown pattern '{}', expected random, glider, blinker, center-pulse or empty",
+                value
+            )),
+        }
+    }
+
+    // (row, col) of the cells the pattern sets on a width x height grid, wrapping
+    // around the edges of grids too small to hold it
+    pub fn cells(self, width: usize, height: usize) -> Vec<(usize, usize)> {
+        match self {
+            Pattern::Random | Pattern::Empty => Vec::new(),
+            Pattern::Glider => glider_cells(width, height),
+            Pattern::Blinker => blinker_cells(width, height),
+            Pattern::CenterPulse => pulse_cells(width, height, 2),
+        }
+    }
+}
+
+fn glider_cells(width: usize, height: usize) -> Vec<(usize, usize)> {
+    place(&[(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)], width, height)
+}
+
+fn blinker_cells(width: usize, height: usize) -> Vec<(usize, usize)> {
+    place(&[(1, 0), (1, 1), (1, 2)], width, height)
+}
+
+fn pulse_cells(width: usize, height: usize, radius: isize) -> Vec<(usize, usize)> {
+    let mut offsets = Vec::new();
+    for i in -radius..=radius {
+        for j in -radius..=radius {
+            if i * i + j * j <= radius * radius {
+                offsets.push((i + radius, j + radius));
+            }
+        }
+    }
+    place(&offsets, width, height)
+}
+
+// Centers a shape given as offsets from its top-left corner, deduplicating cells that
+// land on each other after wrapping
+fn place(offsets: &[(isize, isize)], width: usize, height: usize) -> Vec<(usize, usize)> {
+    let extent_rows = offsets.iter().map(|&(i, _)| i).max().unwrap_or(0) + 1;
+    let extent_cols = offsets.iter().map(|&(_, j)| j).max().unwrap_or(0) + 1;
+    let top = height as isize / 2 - extent_rows / 2;
+    let left = width as isize / 2 - extent_cols / 2;
+
+    let mut cells = Vec::with_capacity(offsets.len());
+    for &(i, j) in offsets {
+        let cell = (
+            (top + i).rem_euclid(height as isize) as usize,
+            (left + j).rem_euclid(width as isize) as usize,
+        );
+        if !cells.contains(&cell) {
+            cells.push(cell);
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place_centers_the_shape() {
+        assert_eq!(
+            glider_cells(20, 10),
+            vec![(4, 10), (5, 11), (6, 9), (6, 10), (6, 11)]
+        );
+        assert_eq!(blinker_cells(9, 9), vec![(4, 3), (4, 4), (4, 5)]);
+    }
+
+    #[test]
+    fn place_wraps_and_deduplicates_on_small_grids() {
+        assert_eq!(blinker_cells(2, 2), vec![(1, 0), (1, 1)]);
+        assert_eq!(pulse_cells(1, 1, 2), vec![(0, 0)]);
+        for (width, height) in [(1, 1), (2, 3), (3, 2)] {
+            let cells = pulse_cells(width, height, 2);
+            assert!(cells.iter().all(|&(row, col)| row < height && col < width));
+            assert_eq!(cells.len(), width * height);
+        }
+    }
+
+    #[test]
+    fn random_and_empty_set_no_cells() {
+        assert!(Pattern::Random.cells(8, 8).is_empty());
+        assert!(Pattern::Empty.cells(8, 8).is_empty());
+    }
+}
@@ -0,0 +1,550 @@
+use std::f64::consts::{PI, TAU};
+
+use log::info;
+
+use crate::grid::{dominant_state, is_undecided, wrapped_offset, CellState, Grid};
+
+// Display color of a basic state, by index
+pub(crate) fn state_color(state: usize) -> [f32; 4] {
+    match state {
+        0 => [0.5, 0.5, 0.8, 1.0], // Color for state 'One'
+        1 => [0.8, 0.5, 0.5, 1.0], // Color for state 'MinusOne'
+        2 => [0.5, 0.8, 0.5, 1.0], // Color for state 'ComplexI'
+        3 => [0.8, 0.8, 0.5, 1.0], // Color for state 'ComplexMinusI'
+        _ => [0.5, 0.5, 0.5, 1.0], // Default or error color
+    }
+}
+
+// Curve applied to grayscale brightness, lifting faint detail out of the dark end
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum ToneCurve {
+    Linear,
+    Sqrt,
+    Log,
+    Gamma,
+}
+
+impl ToneCurve {
+    pub fn next(self) -> ToneCurve {
+        match self {
+            ToneCurve::Linear => ToneCurve::Sqrt,
+            ToneCurve::Sqrt => ToneCurve::Log,
+            ToneCurve::Log => ToneCurve::Gamma,
+            ToneCurve::Gamma => ToneCurve::Linear,
+        }
+    }
+}
+
+const GAMMA_MIN: f64 = 0.1;
+const GAMMA_MAX: f64 = 5.0;
+
+#[derive(Clone, Debug, Copy)]
+pub struct ToneMapping {
+    pub curve: ToneCurve,
+    pub gamma: f64, // Exponent used by the gamma curve, brightness = value^(1/gamma)
+}
+
+impl ToneMapping {
+    pub fn new() -> ToneMapping {
+        ToneMapping {
+            curve: ToneCurve::Linear,
+            gamma: 2.2,
+        }
+    }
+
+    // Map a value in [0, 1] to a brightness in [0, 1]
+    pub fn apply(&self, value: f64) -> f64 {
+        let value = value.clamp(0.0, 1.0);
+        match self.curve {
+            ToneCurve::Linear => value,
+            ToneCurve::Sqrt => value.sqrt(),
+            ToneCurve::Log => (1.0 + 100.0 * value).ln() / 101.0_f64.ln(),
+            ToneCurve::Gamma => value.powf(1.0 / self.gamma),
+        }
+    }
+
+    pub fn adjust_gamma(&mut self, delta: f64) {
+        self.gamma = ((self.gamma + delta) * 10.0).round() / 10.0;
+        self.gamma = self.gamma.clamp(GAMMA_MIN, GAMMA_MAX);
+        info!("Tone gamma: {:.1}", self.gamma);
+    }
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping::new()
+    }
+}
+
+// Pinned obstacle cells are drawn in this color in every render mode
+const OBSTACLE_COLOR: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
+
+// Cells with no leading state, e.g. the background of a pattern grid, so the pattern
+// stands out instead of sharing state 'One''s color
+const UNDECIDED_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+
+// How a cell's probabilities become a color in the dominant state and partner modes
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum Colormap {
+    Classic,   // Flat color of the dominant state
+    Viridis,   // Dominant probability on a viridis-like ramp
+    Grayscale, // Dominant probability as brightness
+    PhaseHue,  // Phase of the state as hue, its magnitude as brightness
+}
+
+impl Colormap {
+    pub fn next(self) -> Colormap {
+        match self {
+            Colormap::Classic => Colormap::Viridis,
+            Colormap::Viridis => Colormap::Grayscale,
+            Colormap::Grayscale => Colormap::PhaseHue,
+            Colormap::PhaseHue => Colormap::Classic,
+        }
+    }
+
+    pub fn color(self, probabilities: &[f64; 4]) -> [f32; 4] {
+        let dominant = dominant_state(probabilities);
+        // Dominant probability rescaled from [0.25, 1], its range, to [0, 1]
+        let certainty = ((probabilities[dominant] - 0.25) / 0.75).clamp(0.0, 1.0);
+        match self {
+            Colormap::Classic if is_undecided(probabilities) => UNDECIDED_COLOR,
+            Colormap::Classic => state_color(dominant),
+            Colormap::Viridis => viridis(certainty),
+            Colormap::Grayscale => {
+                let brightness = certainty as f32;
+                [brightness, brightness, brightness, 1.0]
+            }
+            Colormap::PhaseHue => {
+                let (re, im) = phasor(probabilities);
+                let hue = im.atan2(re).rem_euclid(TAU) / TAU;
+                let value = (re * re + im * im).sqrt().clamp(0.0, 1.0);
+                hsv_to_rgb(hue, 1.0, value)
+            }
+        }
+    }
+}
+
+// Net weight along the real (1, -1) and imaginary (i, -i) axes
+fn phasor(probabilities: &[f64; 4]) -> (f64, f64) {
+    (
+        probabilities[0] - probabilities[1],
+        probabilities[2] - probabilities[3],
+    )
+}
+
+// Phase difference from `from` to `to`, wrapped into [-PI, PI)
+fn phase_step(from: &[f64; 4], to: &[f64; 4]) -> f64 {
+    let (re_from, im_from) = phasor(from);
+    let (re_to, im_to) = phasor(to);
+    (im_to.atan2(re_to) - im_from.atan2(re_from) + PI).rem_euclid(TAU) - PI
+}
+
+// Piecewise-linear approximation of matplotlib's viridis colormap, t in [0, 1]
+fn viridis(t: f64) -> [f32; 4] {
+    const STOPS: [[f64; 3]; 5] = [
+        [0.267, 0.005, 0.329],
+        [0.229, 0.322, 0.546],
+        [0.128, 0.567, 0.551],
+        [0.369, 0.789, 0.383],
+        [0.993, 0.906, 0.144],
+    ];
+    let scaled = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let index = (scaled.floor() as usize).min(STOPS.len() - 2);
+    let frac = scaled - index as f64;
+    let (low, high) = (STOPS[index], STOPS[index + 1]);
+    let channel = |c: usize| (low[c] + (high[c] - low[c]) * frac) as f32;
+    [channel(0), channel(1), channel(2), 1.0]
+}
+
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [f32; 4] {
+    let sector = hue * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match sector as usize {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [(r + m) as f32, (g + m) as f32, (b + m) as f32, 1.0]
+}
+
+// How cells are colored when the grid is drawn
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum RenderMode {
+    DominantState, // Flat color of the most probable basic state
+    Contours,      // Grayscale 'One' probability with lines at evenly spaced iso-levels
+    Difference,    // Brightness shows how far each cell has drifted from the initial grid
+    Partner,       // Color of the entangled partner's dominant state; gray if unentangled
+    PhaseGradient, // Direction of the phase gradient as hue, its steepness as brightness
+}
+
+impl RenderMode {
+    pub fn next(self) -> RenderMode {
+        match self {
+            RenderMode::DominantState => RenderMode::Contours,
+            RenderMode::Contours => RenderMode::Difference,
+            RenderMode::Difference => RenderMode::Partner,
+            RenderMode::Partner => RenderMode::PhaseGradient,
+            RenderMode::PhaseGradient => RenderMode::DominantState,
+        }
+    }
+
+    pub fn cell_color(
+        self,
+        grid: &Grid,
+        initial_cells: &[Vec<CellState>],
+        tone: &ToneMapping,
+        colormap: Colormap,
+        i: usize,
+        j: usize,
+    ) -> [f32; 4] {
+        if grid.obstacles[i][j] {
+            return OBSTACLE_COLOR;
+        }
+
+        let state = &grid.cells[i][j];
+        match self {
+            RenderMode::DominantState => colormap.color(&state.state_probabilities),
+            RenderMode::Contours => {
+                let brightness = tone.apply(state.state_probabilities[0]) as f32;
+                [brightness, brightness, brightness, 1.0]
+            }
+            RenderMode::Difference => {
+                let brightness = tone.apply(grid.drift_from(initial_cells, i, j)) as f32;
+                [brightness, brightness, brightness, 1.0]
+            }
+            RenderMode::Partner => match state.entangled_partner {
+                Some((partner_row, partner_col)) => {
+                    let partner = &grid.cells[partner_row][partner_col];
+                    colormap.color(&partner.state_probabilities)
+                }
+                None => [0.3, 0.3, 0.3, 1.0],
+            },
+            RenderMode::PhaseGradient => {
+                // Forward differences to the right and lower neighbors, wrapping like the grid
+                let right = &grid.cells[i][(j + 1) % grid.width].state_probabilities;
+                let below = &grid.cells[(i + 1) % grid.height][j].state_probabilities;
+                let dx = phase_step(&state.state_probabilities, right);
+                let dy = phase_step(&state.state_probabilities, below);
+                let hue = dy.atan2(dx).rem_euclid(TAU) / TAU;
+                let value = tone.apply(((dx * dx + dy * dy).sqrt() / PI).min(1.0));
+                hsv_to_rgb(hue, 1.0, value)
+            }
+        }
+    }
+}
+
+// The grid colored as the window draws it (without overlays), as tightly packed
+// RGBA8 rows. Returns the image width, height and pixel data.
+pub fn frame_rgba(
+    grid: &Grid,
+    initial_cells: &[Vec<CellState>],
+    view: &View,
+    render_mode: RenderMode,
+    tone: &ToneMapping,
+    colormap: Colormap,
+) -> (usize, usize, Vec<u8>) {
+    // Exported frames always use the unzoomed cell size
+    let view = View { zoom: 1.0, ..*view };
+    let width = grid.width * view.cell_size;
+    let height = grid.height * view.cell_size;
+    let mut pixels = vec![0; width * height * 4];
+
+    for i in 0..grid.height {
+        for j in 0..grid.width {
+            let color = render_mode.cell_color(grid, initial_cells, tone, colormap, i, j);
+            let rgba = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+            let (x, y) = view.cell_position(i, j, grid.width, grid.height);
+            for py in y as usize..y as usize + view.cell_size {
+                for px in x as usize..x as usize + view.cell_size {
+                    let offset = (py * width + px) * 4;
+                    pixels[offset..offset + 4].copy_from_slice(&rgba);
+                }
+            }
+        }
+    }
+
+    (width, height, pixels)
+}
+
+// One pixel per cell, colored by the caller, as tightly packed RGBA8 rows of
+// `width * height * 4` bytes. examples/render_closure.rs shades cells by their
+// dominant probability this way.
+pub fn render_to_rgba(cells: &[Vec<CellState>], color: impl Fn(&CellState) -> [u8; 4]) -> Vec<u8> {
+    cells.iter().flatten().flat_map(color).collect()
+}
+
+// Iso-level band that a probability falls into when the [0, 1] range is split into `levels`
+pub(crate) fn contour_level(probability: f64, levels: usize) -> usize {
+    (probability * levels as f64).floor() as usize
+}
+
+// Limits on the view zoom; beyond these cells become sub-pixel or absurdly large
+pub(crate) const ZOOM_MIN: f64 = 0.25;
+pub(crate) const ZOOM_MAX: f64 = 8.0;
+pub(crate) const ZOOM_STEP: f64 = 1.25; // Zoom factor applied per key press
+
+// Maps between grid cells and window pixels. The grid is toroidal, so panning
+// wraps cells that scroll off one edge back in on the opposite edge.
+#[derive(Clone, Debug, Copy)]
+pub struct View {
+    pub cell_size: usize, // Size of each cell in pixels at zoom 1
+    pub zoom: f64,        // Scale applied to cell_size, within [ZOOM_MIN, ZOOM_MAX]
+    pub flip_y: bool,     // Row 0 is drawn at the top unless flipped
+    pub pan_x: f64,       // Horizontal pan in cells
+    pub pan_y: f64,       // Vertical pan in cells
+    pub wrap: bool,       // Pan around the torus; when false the view stops at the grid edges
+}
+
+impl View {
+    pub fn new(cell_size: usize) -> View {
+        View {
+            cell_size,
+            zoom: 1.0,
+            flip_y: false,
+            pan_x: 0.0,
+            pan_y: 0.0,
+            wrap: true,
+        }
+    }
+
+    // Width and height of a cell on screen, in pixels
+    pub fn cell_pixels(&self) -> f64 {
+        self.cell_size as f64 * self.zoom
+    }
+
+    // Multiplies the zoom by `factor`, clamped to [ZOOM_MIN, ZOOM_MAX]
+    pub fn zoom_by(&mut self, factor: f64) {
+        let zoom = (self.zoom * factor).clamp(ZOOM_MIN, ZOOM_MAX);
+        if zoom == self.zoom {
+            info!("Zoom is already at its limit of {}", zoom);
+        }
+        self.zoom = zoom;
+    }
+
+    // Zooms by `factor` while keeping the grid point under window position `pos` in place
+    pub fn zoom_at(&mut self, factor: f64, pos: [f64; 2]) {
+        let before = self.cell_pixels();
+        self.zoom_by(factor);
+        let after = self.cell_pixels();
+        self.pan_x += pos[0] / before - pos[0] / after;
+        // A flipped view counts rows up from the bottom, so it pans the other way
+        if self.flip_y {
+            self.pan_y -= pos[1] / before - pos[1] / after;
+        } else {
+            self.pan_y += pos[1] / before - pos[1] / after;
+        }
+    }
+
+    // Top-left pixel of the cell at grid row `i`, column `j`
+    pub fn cell_position(
+        &self,
+        i: usize,
+        j: usize,
+        grid_width: usize,
+        grid_height: usize,
+    ) -> (f64, f64) {
+        let col =
+            (j as isize - self.pan_x.round() as isize).rem_euclid(grid_width as isize) as usize;
+        let row =
+            (i as isize - self.pan_y.round() as isize).rem_euclid(grid_height as isize) as usize;
+        let row = if self.flip_y {
+            grid_height - 1 - row
+        } else {
+            row
+        };
+        let cell_pixels = self.cell_pixels();
+        (col as f64 * cell_pixels, row as f64 * cell_pixels)
+    }
+
+    // Grid (row, column) under a window position, or None if it lies outside the grid
+    pub fn cell_at(
+        &self,
+        pos: [f64; 2],
+        grid_width: usize,
+        grid_height: usize,
+    ) -> Option<(usize, usize)> {
+        if pos[0] < 0.0 || pos[1] < 0.0 {
+            return None;
+        }
+
+        let col = (pos[0] / self.cell_pixels()) as usize;
+        let row = (pos[1] / self.cell_pixels()) as usize;
+        if col >= grid_width || row >= grid_height {
+            return None;
+        }
+
+        let row = if self.flip_y {
+            grid_height - 1 - row
+        } else {
+            row
+        };
+        let i = (row as isize + self.pan_y.round() as isize).rem_euclid(grid_height as isize);
+        let j = (col as isize + self.pan_x.round() as isize).rem_euclid(grid_width as isize);
+        Some((i as usize, j as usize))
+    }
+
+    // Columns and rows that fit in a window of `window` pixels at the current zoom
+    pub fn visible_span(&self, window: [f64; 2]) -> (f64, f64) {
+        let cell_pixels = self.cell_pixels();
+        (window[0] / cell_pixels, window[1] / cell_pixels)
+    }
+
+    // With wrapping off, keeps the pan where no wrapped-around cells are on screen
+    pub fn clamp_pan(&mut self, grid_width: usize, grid_height: usize, window: [f64; 2]) {
+        if self.wrap {
+            return;
+        }
+
+        // Cells beyond the visible span
+        let (span_x, span_y) = self.visible_span(window);
+        let max_x = (grid_width as f64 - span_x).max(0.0).floor();
+        let max_y = (grid_height as f64 - span_y).max(0.0).floor();
+        self.pan_x = self.pan_x.clamp(0.0, max_x);
+        // A flipped view shows the last rows first, so it pans the other way from them
+        self.pan_y = if self.flip_y {
+            self.pan_y.clamp(-max_y, 0.0)
+        } else {
+            self.pan_y.clamp(0.0, max_y)
+        };
+    }
+
+    // Eases the pan a fraction `rate` of the way toward centering grid point (col, row)
+    // in a window of `window` pixels, going around the torus when that is shorter
+    pub fn follow(
+        &mut self,
+        target: (f64, f64),
+        rate: f64,
+        grid_width: usize,
+        grid_height: usize,
+        window: [f64; 2],
+    ) {
+        let (width, height) = (grid_width as f64, grid_height as f64);
+        let (span_x, span_y) = self.visible_span(window);
+        let pan_x = target.0 - span_x / 2.0;
+        // A flipped view counts rows up from the bottom, so its center row is offset
+        let pan_y = if self.flip_y {
+            target.1 - height + span_y / 2.0
+        } else {
+            target.1 - span_y / 2.0
+        };
+        self.pan_x += wrapped_offset(pan_x - self.pan_x, width) * rate;
+        self.pan_y += wrapped_offset(pan_y - self.pan_y, height) * rate;
+    }
+
+    // Moves the grid along with a cursor drag of (dx, dy) pixels
+    pub fn drag(&mut self, dx: f64, dy: f64) {
+        let cell_pixels = self.cell_pixels();
+        self.pan_x -= dx / cell_pixels;
+        if self.flip_y {
+            self.pan_y += dy / cell_pixels;
+        } else {
+            self.pan_y -= dy / cell_pixels;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{Entanglement, InitDistribution};
+
+    #[test]
+    fn render_to_rgba_gives_four_bytes_per_cell() {
+        let grid = Grid::new_seeded(13, 7, 2).unwrap();
+        let rgba = render_to_rgba(&grid.cells, |cell| {
+            [(cell.state_probabilities[0] * 255.0) as u8, 0, 0, 255]
+        });
+        assert_eq!(rgba.len(), 13 * 7 * 4);
+        // Rows are packed top to bottom, left to right
+        let last = &grid.cells[6][12];
+        assert_eq!(
+            rgba[rgba.len() - 4],
+            (last.state_probabilities[0] * 255.0) as u8
+        );
+    }
+
+    #[test]
+    fn toroidal_frames_are_continuous_across_the_seam() {
+        // Evolving a copy shifted by half the width must give the same frame shifted by
+        // half the width, so the original left and right edges join like any other columns
+        let (width, shift) = (32, 16);
+        let entanglement = Entanglement {
+            probability: 0.0,
+            ..Entanglement::default()
+        };
+        let mut grid =
+            Grid::with_entanglement(width, 16, InitDistribution::Uniform, entanglement, Some(3))
+                .unwrap();
+        grid.randomness_factor = 0.0;
+        grid.inject_wave_packet((1.0, 8.0), 2.0, -0.8, 0.0);
+        let mut shifted = grid.resized(width, 16, InitDistribution::Uniform).unwrap();
+        shifted.randomness_factor = 0.0;
+        shifted.cells = grid.cells.clone();
+        for row in &mut shifted.cells {
+            row.rotate_left(shift);
+        }
+        grid.evolve(6);
+        shifted.evolve(6);
+
+        let frame = |grid: &Grid| {
+            let view = View::new(2);
+            let tone = ToneMapping::new();
+            frame_rgba(
+                grid,
+                &grid.cells,
+                &view,
+                RenderMode::DominantState,
+                &tone,
+                Colormap::Grayscale,
+            )
+        };
+        let (frame_width, _, pixels) = frame(&grid);
+        let (_, _, shifted_pixels) = frame(&shifted);
+        for (row, shifted_row) in pixels
+            .chunks(frame_width * 4)
+            .zip(shifted_pixels.chunks(frame_width * 4))
+        {
+            let mut expected = row.to_vec();
+            expected.rotate_left(shift * 2 * 4);
+            assert_eq!(expected, shifted_row);
+        }
+    }
+
+    #[test]
+    fn clamp_pan_uses_the_window_span_after_a_resize() {
+        // A 100x100 grid of 7-pixel cells doubled to 200x200 and zoomed out to fit
+        let mut view = View::new(7);
+        view.zoom = 0.5;
+        view.wrap = false;
+        view.pan_x = 50.0;
+        view.pan_y = 50.0;
+        view.clamp_pan(200, 200, [700.0, 700.0]);
+        assert_eq!((view.pan_x, view.pan_y), (0.0, 0.0));
+
+        // Zoomed back in, half the grid fits and the pan can cover the other half
+        view.zoom = 1.0;
+        view.pan_x = 150.0;
+        view.clamp_pan(200, 200, [700.0, 700.0]);
+        assert_eq!(view.pan_x, 100.0);
+    }
+
+    #[test]
+    fn follow_centers_the_target_in_the_window() {
+        // 50x50 of the 200x200 grid fit in the window, so the middle is 25 cells in
+        let mut view = View::new(7);
+        view.follow((100.0, 100.0), 1.0, 200, 200, [350.0, 350.0]);
+        assert_eq!((view.pan_x, view.pan_y), (75.0, 75.0));
+        assert_eq!(view.cell_at([175.0, 175.0], 200, 200), Some((100, 100)));
+
+        // Rows count up in a flipped view, so row 100 now ends at the middle of the window
+        view.flip_y = true;
+        view.follow((100.0, 100.0), 1.0, 200, 200, [350.0, 350.0]);
+        let (_, y) = view.cell_position(100, 100, 200, 200);
+        assert_eq!(y + view.cell_pixels(), 175.0);
+    }
+}
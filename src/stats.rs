@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Collects how long each rendered frame took, for a percentile report on exit
+pub(crate) struct FrameStats {
+    frame_times: Vec<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl FrameStats {
+    pub(crate) fn new() -> FrameStats {
+        FrameStats {
+            frame_times: Vec::new(),
+            last_frame: None,
+        }
+    }
+
+    pub(crate) fn record_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.frame_times.push(now - last_frame);
+        }
+        self.last_frame = Some(now);
+    }
+
+    // Prints p50/p95/p99/max frame times and a coarse histogram, which shows
+    // occasional hitches that an average FPS figure hides
+    pub(crate) fn print_report(&self) {
+        if self.frame_times.is_empty() {
+            println!("No frames recorded");
+            return;
+        }
+
+        let mut sorted = self.frame_times.clone();
+        sorted.sort();
+        let percentile = |p: f64| {
+            let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+            sorted[index].as_secs_f64() * 1000.0
+        };
+        println!(
+            "Frame times over {} frames: p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, max {:.2} ms",
+            sorted.len(),
+            percentile(0.50),
+            percentile(0.95),
+            percentile(0.99),
+            percentile(1.0)
+        );
+
+        let bucket_limits_ms = [4.0, 8.0, 16.7, 33.3, 66.7, f64::INFINITY];
+        let mut counts = [0; 6];
+        for frame_time in &sorted {
+            let ms = frame_time.as_secs_f64() * 1000.0;
+            let bucket = bucket_limits_ms
+                .iter()
+                .position(|&limit| ms < limit)
+                .unwrap_or(bucket_limits_ms.len() - 1);
+            counts[bucket] += 1;
+        }
+
+        let mut lower = 0.0;
+        for (limit, count) in bucket_limits_ms.iter().zip(counts) {
+            let bar_len = count * 50 / sorted.len();
+            let label = if limit.is_finite() {
+                format!("{:>5.1}-{:<5.1} ms", lower, limit)
+            } else {
+                format!("{:>5.1}+      ms", lower)
+            };
+            println!("{} {:>7} {}", label, count, "#".repeat(bar_len));
+            lower = *limit;
+        }
+    }
+}
+
+// Rolling average of how long grid updates take, and the frame rate, shown in the
+// window title
+pub(crate) struct TitleStats {
+    update_times: VecDeque<Duration>,
+    frames: u32,
+    window_start: Instant,
+    fps: f64,
+}
+
+impl TitleStats {
+    const UPDATE_WINDOW: usize = 30; // Updates averaged
+
+    pub(crate) fn new() -> TitleStats {
+        TitleStats {
+            update_times: VecDeque::with_capacity(TitleStats::UPDATE_WINDOW),
+            frames: 0,
+            window_start: Instant::now(),
+            fps: 0.0,
+        }
+    }
+
+    pub(crate) fn record_update(&mut self, elapsed: Duration) {
+        if self.update_times.len() == TitleStats::UPDATE_WINDOW {
+            self.update_times.pop_front();
+        }
+        self.update_times.push_back(elapsed);
+    }
+
+    // Counts a rendered frame. Returns a new title about once a second.
+    pub(crate) fn record_frame(&mut self) -> Option<String> {
+        self.frames += 1;
+        let elapsed = self.window_start.elapsed();
+        if elapsed < Duration::from_secs(1) {
+            return None;
+        }
+        self.fps = self.frames as f64 / elapsed.as_secs_f64();
+        self.frames = 0;
+        self.window_start = Instant::now();
+        Some(self.title())
+    }
+
+    fn title(&self) -> String {
+        if self.update_times.is_empty() {
+            return format!("Quantum Life - {:.0} FPS", self.fps);
+        }
+        let total: Duration = self.update_times.iter().sum();
+        let average_ms = total.as_secs_f64() * 1000.0 / self.update_times.len() as f64;
+        format!(
+            "Quantum Life - {:.0} FPS - update {:.2} ms",
+            self.fps, average_ms
+        )
+    }
+}
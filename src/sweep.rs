@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use log::info;
+
+use crate::config::Config;
+use crate::grid::Grid;
+use crate::io;
+use crate::render::{frame_rgba, Colormap, RenderMode, ToneMapping, View};
+
+// Evolution steps between captured frames
+pub const SWEEP_FRAME_INTERVAL: u32 = 10;
+
+// Sets one config field to a swept value
+type Setter = fn(&mut Config, f64);
+
+// Every config field a sweep can ramp, with how to set it. Count fields are rounded
+// to the nearest whole number.
+const PARAMS: &[(&str, Setter)] = &[
+    ("randomness_factor", |c, v| c.randomness_factor = v),
+    ("collapse_strength", |c, v| c.collapse_strength = v),
+    ("voting_strength", |c, v| c.voting_strength = v),
+    ("survival_min", |c, v| c.survival_min = v.round() as usize),
+    ("survival_max", |c, v| c.survival_max = v.round() as usize),
+    ("birth_count", |c, v| c.birth_count = v.round() as usize),
+    ("entanglement_coupling", |c, v| c.entanglement_coupling = v),
+    ("bistability_strength", |c, v| c.bistability_strength = v),
+    ("hysteresis", |c, v| c.hysteresis = v),
+];
+
+// One parameter ramped linearly from `from` to `to` over `steps` evolution steps
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sweep {
+    pub name: String,
+    pub from: f64,
+    pub to: f64,
+    pub steps: u32,
+}
+
+impl Sweep {
+    // Parses `name:from..to:steps`, e.g. `collapse_strength:0.0..0.3:300`
+    pub fn parse(value: &str) -> Result<Sweep, String> {
+        let fields: Vec<&str> = value.split(':').collect();
+        let [name, range, steps] = fields[..] else {
+            return Err(format!("expected name:from..to:steps, got '{}'", value));
+        };
+        if !PARAMS.iter().any(|(param, _)| *param == name) {
+            let names: Vec<&str> = PARAMS.iter().map(|(param, _)| *param).collect();
+            return Err(format!(
+                "unknown parameter '{}', expected one of {}",
+                name,
+                names.join(", ")
+            ));
+        }
+        let (from, to) = range
+            .split_once("..")
+            .ok_or_else(|| format!("expected a range from..to, got '{}'", range))?;
+        let number = |field: &str| match field.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            _ => Err(format!("invalid number '{}' in '{}'", field, value)),
+        };
+        let steps = match steps.parse::<u32>() {
+            Ok(steps) if steps > 0 => steps,
+            _ => return Err(format!("steps must be at least 1, got '{}'", steps)),
+        };
+        Ok(Sweep {
+            name: name.to_string(),
+            from: number(from)?,
+            to: number(to)?,
+            steps,
+        })
+    }
+
+    // Value of the parameter at `step`, reaching `to` on the last step
+    pub fn value_at(&self, step: u32) -> f64 {
+        self.from + (self.to - self.from) * step as f64 / self.steps as f64
+    }
+
+    // Folder the frames are written to, named for the sweep
+    pub fn folder(&self) -> PathBuf {
+        PathBuf::from(format!("sweep_{}_{}..{}", self.name, self.from, self.to))
+    }
+
+    // The config with the swept parameter set to its value at `step`
+    pub fn config_at(&self, config: &Config, step: u32) -> Config {
+        let mut config = config.clone();
+        if let Some((_, set)) = PARAMS.iter().find(|(param, _)| *param == self.name) {
+            set(&mut config, self.value_at(step));
+        }
+        config
+    }
+}
+
+// Evolves the grid through the sweep, applying the ramped parameter before every step
+// and saving a PNG of the grid every SWEEP_FRAME_INTERVAL steps into `folder`. Returns
+// the number of frames written.
+pub fn run_sweep(
+    grid: &mut Grid,
+    config: &Config,
+    sweep: &Sweep,
+    folder: &Path,
+) -> Result<usize, String> {
+    std::fs::create_dir_all(folder)
+        .map_err(|e| format!("failed to create '{}': {}", folder.display(), e))?;
+    let view = View::new(config.cell_size);
+    let initial = grid.cells.clone();
+    let tone = ToneMapping::new();
+
+    let start = Instant::now();
+    let mut frames = 0;
+    for step in 0..=sweep.steps {
+        grid.apply_config(&sweep.config_at(config, step));
+        if step > 0 {
+            grid.update();
+        }
+        if step % SWEEP_FRAME_INTERVAL == 0 || step == sweep.steps {
+            let (width, height, pixels) = frame_rgba(
+                grid,
+                &initial,
+                &view,
+                RenderMode::DominantState,
+                &tone,
+                Colormap::Classic,
+            );
+            let path = folder.join(format!("step_{:06}.png", step));
+            io::save_png(&path, width, height, &pixels)
+                .map_err(|e| format!("failed to write '{}': {}", path.display(), e))?;
+            frames += 1;
+            info!(
+                "Sweep step {}/{}: {} = {:.4}",
+                step,
+                sweep.steps,
+                sweep.name,
+                sweep.value_at(step)
+            );
+        }
+    }
+    info!(
+        "Swept {} from {} to {} over {} steps in {:.1} s, {} frames in {}",
+        sweep.name,
+        sweep.from,
+        sweep.to,
+        sweep.steps,
+        start.elapsed().as_secs_f64(),
+        frames,
+        folder.display()
+    );
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_name_range_and_steps() {
+        let sweep = Sweep::parse("collapse_strength:0.0..0.3:300").unwrap();
+        assert_eq!(sweep.name, "collapse_strength");
+        assert_eq!((sweep.from, sweep.to, sweep.steps), (0.0, 0.3, 300));
+        assert_eq!(sweep.value_at(0), 0.0);
+        assert!((sweep.value_at(150) - 0.15).abs() < 1e-12);
+        assert_eq!(sweep.value_at(300), 0.3);
+
+        assert!(Sweep::parse("damping:0.0..0.3:300").is_err());
+        assert!(Sweep::parse("hysteresis:0.0-0.3:300").is_err());
+        assert!(Sweep::parse("hysteresis:0.0..NaN:300").is_err());
+        assert!(Sweep::parse("hysteresis:0.0..0.3:0").is_err());
+        assert!(Sweep::parse("hysteresis:0.0..0.3").is_err());
+    }
+
+    #[test]
+    fn config_at_sets_only_the_swept_field() {
+        let config = Config::default();
+        let sweep = Sweep::parse("birth_count:2..4:4").unwrap();
+        let swept = sweep.config_at(&config, 1);
+        assert_eq!(swept.birth_count, 3); // 2.5 rounds away from zero
+        assert_eq!(swept.survival_min, config.survival_min);
+        assert_eq!(swept.hysteresis, config.hysteresis);
+    }
+}